@@ -0,0 +1,216 @@
+use std::fs;
+
+use crate::game::PlainRect;
+use crate::collider::{ HitRegion, HitPart };
+use crate::renderer::{ DrawableBitmap, DrawableAnimation, AnimFrame, AnimMode };
+
+// спрайт атласа; hitbox и pivot относительно левого верхнего угла спрайта
+pub struct Sprite {
+	pub name: String,
+	pub rect: PlainRect,
+	pub pivot: (i32, i32),
+	pub hitbox: PlainRect,
+	pub mask: bool,
+	pub regions: Vec<HitRegion>,
+	pub drawable: DrawableBitmap
+}
+
+pub struct Animation {
+	pub name: String,
+	pub drawable: DrawableAnimation
+}
+
+// содержимое манифеста атласа; загружается один раз и живёт до конца программы,
+// поэтому ссылки на спрайты и их drawable - 'static
+pub struct Atlas {
+	pub sprites: Vec<Sprite>,
+	pub anims: Vec<Animation>
+}
+
+impl Atlas {
+	pub fn sprite(&self, name: &str) -> &Sprite {
+		match self.sprites.iter().find(|s| s.name == name) {
+			Some(sprite) => sprite,
+			None => panic!("atlas: no sprite {}", name)
+		}
+	}
+
+	pub fn anim(&self, name: &str) -> &Animation {
+		match self.anims.iter().find(|a| a.name == name) {
+			Some(anim) => anim,
+			None => panic!("atlas: no animation {}", name)
+		}
+	}
+
+	fn find_sprite_mut(&mut self, name: &str, line_no: usize) -> &mut Sprite {
+		match self.sprites.iter_mut().find(|s| s.name == name) {
+			Some(sprite) => sprite,
+			None => panic!("atlas line {}: unknown sprite {}", line_no, name)
+		}
+	}
+}
+
+pub fn read_file(path: &str, tex_handle: usize) -> &'static Atlas {
+	let text = match fs::read_to_string(path) {
+		Ok(text) => text,
+		Err(err) => panic!("atlas {}: {}", path, err)
+	};
+	Box::leak(Box::new(parse(&text, tex_handle)))
+}
+
+pub fn parse(text: &str, tex_handle: usize) -> Atlas {
+	let mut atlas = Atlas { sprites: Vec::new(), anims: Vec::new() };
+
+	for (i, line) in text.lines().enumerate() {
+		let line_no = i + 1;
+		let words: Vec<&str> = line.split_whitespace().collect();
+		if words.is_empty() || words[0].starts_with('#') {
+			continue;
+		}
+		match words[0] {
+			"sprite" => {
+				let sprite = parse_sprite(&words, line_no, tex_handle);
+				if atlas.sprites.iter().any(|s| s.name == sprite.name) {
+					panic!("atlas line {}: duplicate sprite {}", line_no, sprite.name);
+				}
+				atlas.sprites.push(sprite);
+			},
+			"region" => {
+				if words.len() != 7 {
+					panic!("atlas line {}: region <sprite> <name> <x> <y> <w> <h>", line_no);
+				}
+				let part = match HitPart::from_name(words[2]) {
+					Some(part) => part,
+					None => panic!("atlas line {}: unknown region {}", line_no, words[2])
+				};
+				let rect = parse_rect(&words[3..7], line_no);
+				atlas.find_sprite_mut(words[1], line_no).regions.push(
+					HitRegion { part: part, rect: rect }
+				);
+			},
+			"anim" => {
+				let anim = parse_anim(&atlas, &words, line_no, tex_handle);
+				atlas.anims.push(anim);
+			},
+			other => panic!("atlas line {}: unknown keyword {}", line_no, other)
+		}
+	}
+	atlas
+}
+
+fn parse_sprite(words: &[&str], line_no: usize, tex_handle: usize) -> Sprite {
+	if words.len() < 6 {
+		panic!("atlas line {}: sprite <name> <x> <y> <w> <h>", line_no);
+	}
+	let rect = parse_rect(&words[2..6], line_no);
+	let mut sprite = Sprite {
+		name: words[1].to_string(),
+		rect: rect,
+		pivot: (0, 0),
+		hitbox: PlainRect { x: 0, y: 0, w: rect.w, h: rect.h },
+		mask: false,
+		regions: Vec::new(),
+		drawable: DrawableBitmap { tex_handle: tex_handle, rect: rect, pivot: (0, 0) }
+	};
+
+	let mut pos = 6;
+	while pos < words.len() {
+		match words[pos] {
+			"pivot" if pos + 2 < words.len() => {
+				sprite.pivot = (
+					parse_num(words[pos + 1], line_no),
+					parse_num(words[pos + 2], line_no)
+				);
+				pos += 3;
+			},
+			"hitbox" if pos + 4 < words.len() => {
+				sprite.hitbox = parse_rect(&words[pos + 1..pos + 5], line_no);
+				pos += 5;
+			},
+			"mask" => {
+				sprite.mask = true;
+				pos += 1;
+			},
+			other => panic!("atlas line {}: bad sprite option {}", line_no, other)
+		}
+	}
+	sprite.drawable.pivot = sprite.pivot;
+
+	let hitbox = sprite.hitbox;
+	if hitbox.x < 0 || hitbox.y < 0
+		|| hitbox.x as u32 + hitbox.w > rect.w || hitbox.y as u32 + hitbox.h > rect.h
+	{
+		panic!("atlas line {}: hitbox outside sprite {}", line_no, sprite.name);
+	}
+	sprite
+}
+
+fn parse_anim(atlas: &Atlas, words: &[&str], line_no: usize, tex_handle: usize) -> Animation {
+	if words.len() < 4 {
+		panic!("atlas line {}: anim <name> <mode> <sprite>:<duration>[:<dx>,<dy>] ...", line_no);
+	}
+	let mode = match words[2] {
+		"loop" => AnimMode::LOOP,
+		"once" => AnimMode::ONCE,
+		"pingpong" => AnimMode::PINGPONG,
+		other => panic!("atlas line {}: unknown animation mode {}", line_no, other)
+	};
+	let mut frames = Vec::with_capacity(words.len() - 3);
+	let mut pivot = None;
+	for word in words[3..].iter() {
+		let parts: Vec<&str> = word.split(':').collect();
+		if parts.len() < 2 || parts.len() > 3 {
+			panic!("atlas line {}: frame {} is not <sprite>:<duration>[:<dx>,<dy>]", line_no, word);
+		}
+		let name = parts[0];
+		let offset = match parts.get(2) {
+			Some(offset) => match offset.split_once(',') {
+				Some((dx, dy)) => (parse_num(dx, line_no), parse_num(dy, line_no)),
+				None => panic!("atlas line {}: frame offset {} is not <dx>,<dy>", line_no, offset)
+			},
+			None => (0, 0)
+		};
+		let sprite = match atlas.sprites.iter().find(|s| s.name == name) {
+			Some(sprite) => sprite,
+			None => panic!("atlas line {}: unknown sprite {}", line_no, name)
+		};
+		// поворот анимации - вокруг опорной точки первого кадра
+		pivot.get_or_insert(sprite.pivot);
+		frames.push(AnimFrame {
+			rect: sprite.rect,
+			duration: parse_num(parts[1], line_no) as u32,
+			offset: offset
+		});
+	}
+	Animation {
+		name: words[1].to_string(),
+		drawable: DrawableAnimation {
+			tex_handle: tex_handle,
+			frames: Box::leak(frames.into_boxed_slice()),
+			mode: mode,
+			pivot: pivot.unwrap_or((0, 0)),
+			on_done: None
+		}
+	}
+}
+
+fn parse_rect(words: &[&str], line_no: usize) -> PlainRect {
+	let w = parse_num(words[2], line_no);
+	let h = parse_num(words[3], line_no);
+	if w < 0 || h < 0 {
+		panic!("atlas line {}: negative size", line_no);
+	}
+	PlainRect {
+		x: parse_num(words[0], line_no),
+		y: parse_num(words[1], line_no),
+		w: w as u32,
+		h: h as u32
+	}
+}
+
+fn parse_num(word: &str, line_no: usize) -> i32 {
+	match word.parse::<i32>() {
+		Ok(num) => num,
+		Err(_) => panic!("atlas line {}: {} is not a number", line_no, word)
+	}
+}
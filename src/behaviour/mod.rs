@@ -1,173 +1,214 @@
-use crate::game::{ GmoType, GameObject, GmoNew };
+use crate::game::{ GmoType, GameObject };
 use crate::Context;
+use crate::scheduler::{ TimerDelay, TimerHandle };
 
-#[derive(PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum BhvSignal {
+	TIMER, LANDED, HIT
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub enum BhvStatus {
-	OK, END
+	OK, END, SIGNAL(BhvSignal)
 }
 
+// скорость в пикселях за кадр
 pub struct BhvDataMove {
-	pub dx: i32,
-	pub dy: i32
+	pub dx: f32,
+	pub dy: f32
 }
 
 pub struct BhvDataGun {
 	pub wave_type: GmoType,
-	pub cnt: u32,
-	pub delay: u32
+	pub cnt: u32
+}
+
+// TimerHandle::NONE - таймер не заведён
+pub struct BhvDataTimer {
+	pub timer: TimerHandle
 }
 
-pub struct BhvDataTimedMotion {
-	pub speed: i32,
-	pub delay: u32
+// таймер заводится при первом обновлении, первый раз срабатывает через first
+pub struct BhvDataSpawner {
+	pub first: TimerDelay,
+	pub timer: TimerHandle
+}
+
+impl BhvDataSpawner {
+	pub fn new(first: TimerDelay) -> Self {
+		BhvDataSpawner { first: first, timer: TimerHandle::NONE }
+	}
 }
 
-pub trait Behaviour
+// переиспользуемая часть поведения; состояние автомата объединяет несколько
+pub trait Behaviour: Sync
 {
 	fn update(
-		&self, ctx: &mut Context, gmo: &mut GameObject
+		&self, ctx: &mut Context, gmo: &mut GameObject, index: usize
 	) -> BhvStatus;
 
-	fn free(&self, ctx: &mut Context, index: usize);
+	// освободить внешние ресурсы (таймеры) при удалении объекта;
+	// может вызываться несколько раз для общих данных
+	fn release(&self, ctx: &mut Context, gmo: &GameObject) {
+	}
 }
 
-pub struct BehaviourMove {}
+pub type BhvEnter = fn(ctx: &mut Context, gmo: &mut GameObject);
 
-impl Behaviour for BehaviourMove {	// safe
-	fn update(
-		&self, ctx: &mut Context, gmo: &mut GameObject
+// переход в состояние BHV_END завершает жизнь объекта
+pub const BHV_END: usize = usize::MAX;
+
+pub struct BhvTransition {
+	pub signal: BhvSignal,
+	pub to: usize,
+	pub enter: Option<BhvEnter>
+}
+
+pub struct BhvState {
+	pub components: &'static [&'static dyn Behaviour],
+	pub transitions: &'static [BhvTransition]
+}
+
+pub struct BhvMachine {
+	pub states: &'static [BhvState]
+}
+
+impl BhvMachine {
+	pub fn update(
+		&self, ctx: &mut Context, gmo: &mut GameObject, index: usize
 	) -> BhvStatus {
-		let bhv_data = ctx.storage.pantry_bhvd_move.get(gmo.bhvd_index);
-		gmo.data.x += bhv_data.dx;
-		gmo.data.y += bhv_data.dy;
+		let state = &self.states[gmo.bhv_state];
+		for component in state.components.iter() {
+			match component.update(ctx, gmo, index) {
+				BhvStatus::OK => (),
+				BhvStatus::END => return BhvStatus::END,
+				BhvStatus::SIGNAL(signal) => {
+					let state_before = gmo.bhv_state;
+					let status = self.signal(ctx, gmo, signal);
+					// остальные компоненты принадлежат уже прежнему состоянию
+					if status == BhvStatus::END || gmo.bhv_state != state_before {
+						return status;
+					}
+				}
+			}
+		}
 		BhvStatus::OK
 	}
 
-	fn free(&self, ctx: &mut Context, index: usize) {
-		ctx.storage.pantry_bhvd_move.free(index);
+	// сигнал без перехода в текущем состоянии игнорируется
+	pub fn signal(
+		&self, ctx: &mut Context, gmo: &mut GameObject, signal: BhvSignal
+	) -> BhvStatus {
+		let state = &self.states[gmo.bhv_state];
+		match state.transitions.iter().find(|t| t.signal == signal) {
+			Some(transition) => {
+				if transition.to == BHV_END {
+					return BhvStatus::END;
+				}
+				gmo.bhv_state = transition.to;
+				if let Some(enter) = transition.enter {
+					enter(ctx, gmo);
+				}
+				BhvStatus::OK
+			},
+			None => BhvStatus::OK
+		}
+	}
+
+	// компоненты всех состояний, так как данные общие для автомата
+	pub fn release(&self, ctx: &mut Context, gmo: &GameObject) {
+		for state in self.states.iter() {
+			for component in state.components.iter() {
+				component.release(ctx, gmo);
+			}
+		}
 	}
 }
 
-pub struct BehaviourGravityMove {}
+pub struct BehaviourMove {}
 
-impl Behaviour for BehaviourGravityMove {	// safe
+impl Behaviour for BehaviourMove {
 	fn update(
-		&self, ctx: &mut Context, gmo: &mut GameObject
+		&self, ctx: &mut Context, gmo: &mut GameObject, index: usize
 	) -> BhvStatus {
-		let bhv_data = ctx.storage.pantry_bhvd_tm.get_mut(gmo.bhvd_index);
-		gmo.data.y += bhv_data.speed;
-		bhv_data.speed += 1;
-		if gmo.data.y < ctx.stage.h as i32 {
-			return BhvStatus::OK;
-		}
-		BhvStatus::END
+		let bhv_data = ctx.storage.get::<BhvDataMove>(gmo.bhvd.get::<BhvDataMove>());
+		gmo.data.x += bhv_data.dx;
+		gmo.data.y += bhv_data.dy;
+		BhvStatus::OK
 	}
+}
+
+// ускоряет BhvDataMove вниз; ставится перед BehaviourMove
+pub struct BehaviourGravity {}
 
-	fn free(&self, ctx: &mut Context, index: usize) {
-		ctx.storage.pantry_bhvd_tm.free(index);
+impl Behaviour for BehaviourGravity {
+	fn update(
+		&self, ctx: &mut Context, gmo: &mut GameObject, index: usize
+	) -> BhvStatus {
+		let bhv_data = ctx.storage.get_mut::<BhvDataMove>(gmo.bhvd.get::<BhvDataMove>());
+		bhv_data.dy += 1.0;
+		BhvStatus::OK
 	}
 }
 
-pub struct BehaviourGun {}
+// подаёт сигнал TIMER, когда срабатывает таймер из BhvDataTimer
+pub struct BehaviourTimer {}
 
-impl Behaviour for BehaviourGun {	// safe - does not replace gmo
+impl Behaviour for BehaviourTimer {
 	fn update(
-		&self, ctx: &mut Context, gmo: &mut GameObject
+		&self, ctx: &mut Context, gmo: &mut GameObject, index: usize
 	) -> BhvStatus {
-		let bhv_data = ctx.storage.pantry_bhvd_gun.get_mut(gmo.bhvd_index);
-		if bhv_data.cnt > 0 {
-			if bhv_data.delay > 0 {
-				bhv_data.delay -= 1;
-			} else {
-				//bhv_data.cnt -= 1;
-				bhv_data.delay = ctx.rand.randint(100, 500);
-				let mut dx = 1;
-				let mut x = 0;
-				if ctx.rand.randint(0, 2) == 1 {
-					dx = -1;
-					x = 700;
-				}
-				let delay = ctx.rand.randint(10, 20);
-				let gmo_factory = ctx.gmo_factory;
-				let gmc = gmo_factory.spawn_carrier(
-					ctx, x, 40,	BhvDataTimedMotion { speed: dx, delay: delay }
-				);
-				ctx.vec_gmo_new.push(
-					GmoNew {
-						sto: ctx.sto_factory.spawn_carrier(gmc.data.x, gmc.data.y, dx),
-						gmo: gmc
-					}
-				);
-			}
+		let timer = ctx.storage.get::<BhvDataTimer>(gmo.bhvd.get::<BhvDataTimer>()).timer;
+		if ctx.scheduler.take(timer) {
+			return BhvStatus::SIGNAL(BhvSignal::TIMER);
 		}
 		BhvStatus::OK
 	}
 
-	fn free(&self, ctx: &mut Context, index: usize) {
-		ctx.storage.pantry_bhvd_gun.free(index);
+	fn release(&self, ctx: &mut Context, gmo: &GameObject) {
+		let timer = ctx.storage.get::<BhvDataTimer>(gmo.bhvd.get::<BhvDataTimer>()).timer;
+		ctx.scheduler.cancel(timer);
 	}
 }
 
-pub struct BehaviourCarrier {}
+// вызывает spawn через случайные промежутки period
+pub struct BehaviourSpawner {
+	pub spawn: fn(ctx: &mut Context, gmo: &GameObject),
+	pub period: TimerDelay
+}
 
-impl Behaviour for BehaviourCarrier {	// safe - does not replace gmo
+impl Behaviour for BehaviourSpawner {
 	fn update(
-		&self, ctx: &mut Context, gmo: &mut GameObject
+		&self, ctx: &mut Context, gmo: &mut GameObject, index: usize
 	) -> BhvStatus {
-		let bhv_data = ctx.storage.pantry_bhvd_tm.get_mut(gmo.bhvd_index);
-		let speed = bhv_data.speed;
-		if bhv_data.delay > 0 {
-			bhv_data.delay -= 1;
-		} else {
-			bhv_data.delay = ctx.rand.randint(50, 100);
-			let gmo_factory = ctx.gmo_factory;
-			let mut gmt = gmo_factory.spawn_trooper(ctx, gmo.data.x, gmo.data.y + 24);
-			if speed > 0 {
-				gmt.data.x += 54;
-			} else {
-				gmt.data.x += 31;
-			}
-			ctx.vec_gmo_new.push(
-				GmoNew {
-					sto: ctx.sto_factory.spawn_trooper(gmt.data.x, gmt.data.y),
-					gmo: gmt
-				}
-			);
+		let bhv_data = ctx.storage.get_mut::<BhvDataSpawner>(gmo.bhvd.get::<BhvDataSpawner>());
+		if bhv_data.timer == TimerHandle::NONE {
+			bhv_data.timer = ctx.scheduler.repeat(&mut ctx.rand, bhv_data.first, self.period);
+		}
+		if ctx.scheduler.take(bhv_data.timer) {
+			(self.spawn)(ctx, gmo);
 		}
-		gmo.data.x += speed;
 		BhvStatus::OK
 	}
 
-	fn free(&self, ctx: &mut Context, index: usize) {
-		ctx.storage.pantry_bhvd_tm.free(index);
+	fn release(&self, ctx: &mut Context, gmo: &GameObject) {
+		let timer = ctx.storage.get::<BhvDataSpawner>(gmo.bhvd.get::<BhvDataSpawner>()).timer;
+		ctx.scheduler.cancel(timer);
 	}
 }
 
-pub struct BehaviourTrooper {}
+// подаёт сигнал LANDED, когда нижний край объекта касается земли
+pub struct BehaviourLand {}
 
-impl Behaviour for BehaviourTrooper {	// replaces gmo
+impl Behaviour for BehaviourLand {
 	fn update(
-		&self, ctx: &mut Context, gmo: &mut GameObject
+		&self, ctx: &mut Context, gmo: &mut GameObject, index: usize
 	) -> BhvStatus {
-		let bhv_data = ctx.storage.pantry_bhvd_tm.get_mut(gmo.bhvd_index);
-		gmo.data.y += bhv_data.speed;
-		if bhv_data.delay > 0 {
-			bhv_data.delay -= 1;
-			if bhv_data.delay == 0 {
-				// need to replace trooper with chute
-				let gmo_factory = ctx.gmo_factory;
-				let mut gmc = gmo_factory.spawn_chute(ctx, gmo.data.x, gmo.data.y);
-				gmc.data.y -= gmc.data.h - gmo.data.h;
-				gmc.data.x -= ((gmc.data.w - gmo.data.w) >> 1) as i32;
-				let sto = ctx.sto_factory.spawn_chute(gmc.data.x, gmc.data.y);
-				// in-place
-				gmo.update_from(ctx, &gmc, sto);
-			}
+		let ground = (ctx.world.rect.y + ctx.world.rect.h as i32) as f32;
+		if gmo.data.y + gmo.data.h as f32 >= ground {
+			gmo.data.y = ground - gmo.data.h as f32;
+			return BhvStatus::SIGNAL(BhvSignal::LANDED);
 		}
 		BhvStatus::OK
 	}
-
-	fn free(&self, ctx: &mut Context, index: usize) {
-		ctx.storage.pantry_bhvd_tm.free(index);
-	}
 }
@@ -0,0 +1,134 @@
+use sdl2::keyboard::Keycode;
+
+use crate::event::{ EventListener, GameEvent, GameEventKind };
+use crate::xrand::XRand;
+
+// наибольшее смещение тряски в пикселях при trauma = 1
+const SHAKE_MAX: f32 = 10.0;
+// на столько trauma убывает за шаг симуляции
+const TRAUMA_DECAY: f32 = 0.04;
+// при сильной тряске каждый шаг - новое случайное смещение, поэтому
+// у тряски свой генератор: общий ctx.rand определяет ход игры
+const SHAKE_SEED: u32 = 0x5ce4e;
+
+// смещение всех слоёв сцены, кроме HUD; тряска растёт как квадрат trauma,
+// так что слабые удары почти незаметны, а сильные складываются
+pub struct Camera {
+	pub x: f32,
+	pub y: f32,
+	trauma: f32,
+	shake_x: f32,
+	shake_y: f32,
+	prev_shake_x: f32,
+	prev_shake_y: f32,
+	shake_enabled: bool,
+	rand: XRand
+}
+
+impl Camera {
+	pub fn new() -> Self {
+		Camera {
+			x: 0.0,
+			y: 0.0,
+			trauma: 0.0,
+			shake_x: 0.0,
+			shake_y: 0.0,
+			prev_shake_x: 0.0,
+			prev_shake_y: 0.0,
+			shake_enabled: true,
+			rand: XRand::from_seed(SHAKE_SEED)
+		}
+	}
+
+	pub fn reset(&mut self) {
+		self.x = 0.0;
+		self.y = 0.0;
+		self.stop_shake();
+	}
+
+	// тряску можно отключить совсем, например из-за укачивания
+	pub fn set_shake_enabled(&mut self, enabled: bool) {
+		self.shake_enabled = enabled;
+		if !enabled {
+			self.stop_shake();
+		}
+	}
+
+	pub fn add_trauma(&mut self, amount: f32) {
+		if self.shake_enabled {
+			self.trauma = (self.trauma + amount).min(1.0);
+		}
+	}
+
+	// шаг симуляции: затухание и новое смещение тряски
+	pub fn update(&mut self) {
+		self.prev_shake_x = self.shake_x;
+		self.prev_shake_y = self.shake_y;
+		if self.trauma <= 0.0 {
+			self.shake_x = 0.0;
+			self.shake_y = 0.0;
+			return;
+		}
+		let amount = SHAKE_MAX * self.trauma * self.trauma;
+		self.shake_x = amount * self.random_unit();
+		self.shake_y = amount * self.random_unit();
+		self.trauma = (self.trauma - TRAUMA_DECAY).max(0.0);
+	}
+
+	// смещение для отрисовки; alpha - доля шага, как у Stage::transform
+	pub fn offset(&self, alpha: f32) -> (i32, i32) {
+		let sx = self.prev_shake_x + (self.shake_x - self.prev_shake_x) * alpha;
+		let sy = self.prev_shake_y + (self.shake_y - self.prev_shake_y) * alpha;
+		((self.x + sx).round() as i32, (self.y + sy).round() as i32)
+	}
+
+	// F12 - включить или выключить тряску
+	pub fn handle_key(&mut self, keycode: Keycode) -> bool {
+		if keycode != Keycode::F12 {
+			return false;
+		}
+		let enabled = !self.shake_enabled;
+		self.set_shake_enabled(enabled);
+		true
+	}
+
+	fn stop_shake(&mut self) {
+		self.trauma = 0.0;
+		self.shake_x = 0.0;
+		self.shake_y = 0.0;
+		self.prev_shake_x = 0.0;
+		self.prev_shake_y = 0.0;
+	}
+
+	// равномерно в -1..=1
+	fn random_unit(&mut self) -> f32 {
+		self.rand.randint(0, 2001) as f32 / 1000.0 - 1.0
+	}
+}
+
+impl Default for Camera {
+	fn default() -> Self {
+		Camera::new()
+	}
+}
+
+impl EventListener for Camera {
+	fn on_event(&mut self, evt: &GameEvent) {
+		self.add_trauma(match evt.kind {
+			GameEventKind::CarrierDestroyed => 0.4,
+			GameEventKind::GunHit => 0.7,
+			_ => 0.0
+		});
+	}
+}
+
+pub fn is_camera_key(keycode: Keycode) -> bool {
+	keycode == Keycode::F12
+}
+
+// --no-shake из командной строки
+pub fn parse_args(camera: &mut Camera, args: &[String]) {
+	if args.iter().any(|arg| arg == "--no-shake") {
+		camera.set_shake_enabled(false);
+	}
+}
@@ -0,0 +1,171 @@
+use sdl2::keyboard::Keycode;
+
+// скорость задаётся в четвертях: 1 - 0.25x, 4 - 1x, 16 - 4x
+pub const SPEED_MIN: u32 = 1;
+pub const SPEED_NORMAL: u32 = 4;
+pub const SPEED_MAX: u32 = 16;
+
+// сколько шагов симуляции сделать за кадр; сами шаги всегда одинаковой
+// длины, поэтому скорость и пауза не влияют на результат симуляции
+pub struct SimClock {
+	step_ms: u32,
+	max_steps: u32,
+	speed: u32,
+	paused: bool,
+	step_requested: bool,
+	accumulator: u32		// в четвертях миллисекунды
+}
+
+impl SimClock {
+	pub fn new(step_ms: u32, max_steps: u32) -> Self {
+		SimClock {
+			step_ms: step_ms,
+			max_steps: max_steps,
+			speed: SPEED_NORMAL,
+			paused: false,
+			step_requested: false,
+			accumulator: 0
+		}
+	}
+
+	pub fn speed(&self) -> f32 {
+		self.speed as f32 / SPEED_NORMAL as f32
+	}
+
+	pub fn set_speed(&mut self, speed: u32) {
+		self.speed = speed.clamp(SPEED_MIN, SPEED_MAX);
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	pub fn set_paused(&mut self, paused: bool) {
+		self.paused = paused;
+		self.accumulator = 0;
+	}
+
+	// ровно один шаг при следующем advance, только на паузе
+	pub fn request_step(&mut self) {
+		if self.paused {
+			self.step_requested = true;
+		}
+	}
+
+	// F5 - пауза, F6 - один шаг, F7 - медленнее, F8 - быстрее, F9 - 1x
+	pub fn handle_key(&mut self, keycode: Keycode) -> bool {
+		if !is_debug_key(keycode) {
+			return false;
+		}
+		match keycode {
+			Keycode::F5 => {
+				let paused = !self.paused;
+				self.set_paused(paused);
+			},
+			Keycode::F6 => self.request_step(),
+			Keycode::F7 => self.set_speed(self.speed / 2),
+			Keycode::F8 => self.set_speed(self.speed * 2),
+			Keycode::F9 => self.set_speed(SPEED_NORMAL),
+			_ => ()
+		}
+		true
+	}
+
+	// число шагов за прошедшее реальное время; лишнее время после
+	// долгой паузы отбрасывается
+	pub fn advance(&mut self, elapsed_ms: u32) -> u32 {
+		if self.paused {
+			let steps = self.step_requested as u32;
+			self.step_requested = false;
+			return steps;
+		}
+
+		let step = self.step_ms * SPEED_NORMAL;
+		// на большой скорости догонять приходится больше шагов
+		let max_steps = self.max_steps * (self.speed / SPEED_NORMAL).max(1);
+		self.accumulator += elapsed_ms * self.speed;
+		let mut steps = self.accumulator / step;
+		if steps > max_steps {
+			steps = max_steps;
+			self.accumulator %= step;
+		} else {
+			self.accumulator -= steps * step;
+		}
+		steps
+	}
+
+	// доля шага, прошедшая после последнего шага симуляции
+	pub fn alpha(&self) -> f32 {
+		if self.paused {
+			return 1.0;
+		}
+		self.accumulator as f32 / (self.step_ms * SPEED_NORMAL) as f32
+	}
+}
+
+pub fn is_debug_key(keycode: Keycode) -> bool {
+	matches!(keycode, Keycode::F5 | Keycode::F6 | Keycode::F7 | Keycode::F8 | Keycode::F9)
+}
+
+// --speed=0.5 и --paused из командной строки
+pub fn parse_args(clock: &mut SimClock, args: &[String]) {
+	for arg in args.iter() {
+		if arg == "--paused" {
+			clock.set_paused(true);
+		} else if let Some(value) = arg.strip_prefix("--speed=") {
+			match value.parse::<f32>() {
+				Ok(speed) if speed > 0.0 => {
+					clock.set_speed((speed * SPEED_NORMAL as f32).round() as u32);
+				},
+				_ => eprintln!("invalid speed: {}", value)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn advance_keeps_remainder() {
+		let mut clock = SimClock::new(10, 5);
+		assert_eq!(clock.advance(25), 2);
+		assert!((clock.alpha() - 0.5).abs() < 1e-6);
+		assert_eq!(clock.advance(5), 1);
+		assert_eq!(clock.alpha(), 0.0);
+	}
+
+	#[test]
+	fn advance_limits_catch_up() {
+		let mut clock = SimClock::new(10, 5);
+		// после долгой задержки не больше max_steps, остаток меньше шага
+		assert_eq!(clock.advance(1005), 5);
+		assert!((clock.alpha() - 0.5).abs() < 1e-6);
+		assert_eq!(clock.advance(0), 0);
+		// на 4x шаги идут вчетверо чаще, и предел вчетверо выше
+		clock.set_speed(SPEED_MAX);
+		assert_eq!(clock.advance(1000), 20);
+		clock.set_speed(SPEED_MIN);
+		assert_eq!(clock.advance(40), 1);
+	}
+
+	#[test]
+	fn pause_and_single_step() {
+		let mut clock = SimClock::new(10, 5);
+		clock.advance(5);
+		clock.set_paused(true);
+		assert_eq!(clock.advance(100), 0);
+		assert_eq!(clock.alpha(), 1.0);
+		clock.request_step();
+		assert_eq!(clock.advance(0), 1);
+		assert_eq!(clock.advance(100), 0);
+		// накопленное до паузы время отброшено
+		clock.set_paused(false);
+		assert_eq!(clock.advance(9), 0);
+		assert_eq!(clock.advance(1), 1);
+		// без паузы запрос шага игнорируется
+		clock.request_step();
+		assert_eq!(clock.advance(0), 0);
+	}
+}
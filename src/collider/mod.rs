@@ -1,17 +1,115 @@
-use crate::game::{ GmoType, GameObject, GmoNew, PlainRect };
+use std::ops::BitOr;
+
+use crate::game::{ GmoType, GameObject, GmoData, PlainRect, GMO_TYPE_CNT };
 use crate::pantry::Pantry;
+use crate::png::Image;
+use crate::atlas::Atlas;
+use crate::event::GameEventKind;
+use crate::behaviour::BhvSignal;
+use crate::factory::spawn_explosion;
 use crate::Context;
 
-//#[repr(u8)]
 #[derive(Copy, Clone, PartialEq, Eq)]
-pub enum CollideGroup {
-	NONE, SHOT, AERIAL, BOMB, FALLING, STANDING, GUN
+pub struct CollideGroup(pub u32);
+
+impl CollideGroup {
+	pub const NONE: CollideGroup = CollideGroup(0);
+	pub const SHOT: CollideGroup = CollideGroup(1 << 0);
+	pub const AERIAL: CollideGroup = CollideGroup(1 << 1);
+	pub const BOMB: CollideGroup = CollideGroup(1 << 2);
+	pub const FALLING: CollideGroup = CollideGroup(1 << 3);
+	pub const STANDING: CollideGroup = CollideGroup(1 << 4);
+	pub const GUN: CollideGroup = CollideGroup(1 << 5);
+
+	pub fn is_empty(self) -> bool {
+		self.0 == 0
+	}
+
+	pub fn intersects(self, other: CollideGroup) -> bool {
+		self.0 & other.0 != 0
+	}
+}
+
+impl BitOr for CollideGroup {
+	type Output = CollideGroup;
+
+	fn bitor(self, other: CollideGroup) -> CollideGroup {
+		CollideGroup(self.0 | other.0)
+	}
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct CollideMask {
-	pub src: CollideGroup,
-	pub dst: CollideGroup
+	pub member_of: CollideGroup,
+	pub collides_with: CollideGroup,
+	pub fast: bool		// проверять с учётом траектории за кадр
+}
+
+impl CollideMask {
+	pub const NONE: CollideMask = CollideMask {
+		member_of: CollideGroup::NONE, collides_with: CollideGroup::NONE, fast: false
+	};
+
+	pub fn is_empty(&self) -> bool {
+		self.member_of.is_empty() && self.collides_with.is_empty()
+	}
+
+	// пара сталкивается, если хотя бы одна сторона бьёт группу другой
+	pub fn test(&self, other: &CollideMask) -> bool {
+		self.collides_with.intersects(other.member_of)
+			|| other.collides_with.intersects(self.member_of)
+	}
+}
+
+pub struct CollideMatrix {
+	masks: [CollideMask; GMO_TYPE_CNT]
+}
+
+impl Default for CollideMatrix {
+	fn default() -> Self {
+		CollideMatrix::new()
+	}
+}
+
+impl CollideMatrix {
+	pub fn new() -> Self {
+		CollideMatrix { masks: [CollideMask::NONE; GMO_TYPE_CNT] }
+	}
+
+	// вся таблица столкновений игры
+	pub fn create() -> Self {
+		let mut matrix = CollideMatrix::new();
+		matrix.set(
+			GmoType::SHOT,
+			CollideGroup::SHOT, CollideGroup::AERIAL
+		);
+		matrix.set_fast(GmoType::SHOT, true);
+		matrix.set(GmoType::CARRIER, CollideGroup::AERIAL, CollideGroup::NONE);
+		matrix.set(GmoType::BOMBER, CollideGroup::AERIAL, CollideGroup::NONE);
+		matrix.set(GmoType::CHUTE, CollideGroup::AERIAL, CollideGroup::NONE);
+		matrix.set(GmoType::FALLING, CollideGroup::AERIAL, CollideGroup::NONE);
+		matrix.set(GmoType::STANDING, CollideGroup::AERIAL, CollideGroup::NONE);
+		matrix.set(GmoType::GUN, CollideGroup::GUN, CollideGroup::NONE);
+		matrix.set(GmoType::BOMB, CollideGroup::BOMB, CollideGroup::NONE);
+		matrix
+	}
+
+	pub fn set(
+		&mut self, gmo_type: GmoType,
+		member_of: CollideGroup, collides_with: CollideGroup
+	) {
+		self.masks[gmo_type as usize] = CollideMask {
+			member_of: member_of, collides_with: collides_with, fast: false
+		};
+	}
+
+	pub fn set_fast(&mut self, gmo_type: GmoType, fast: bool) {
+		self.masks[gmo_type as usize].fast = fast;
+	}
+
+	pub fn get(&self, gmo_type: GmoType) -> CollideMask {
+		self.masks[gmo_type as usize]
+	}
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -21,19 +119,317 @@ pub enum CollideStatus {
 	COLLIDE		// обрабатывать столкновение
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct CollidePair {
 	pub src_index: usize,
 	pub dst_index: usize,
-	pub status: CollideStatus
+	pub status: CollideStatus,
+	pub toi: f32,		// момент столкновения внутри кадра, 0..1
+	pub src_part: Option<HitPart>,
+	pub dst_part: Option<HitPart>
+}
+
+impl CollidePair {
+	// в какую именованную часть спрайта объекта index пришлось попадание
+	pub fn part(&self, index: usize) -> Option<HitPart> {
+		if index == self.src_index {
+			self.src_part
+		} else {
+			self.dst_part
+		}
+	}
+}
+
+// именованные части спрайтов, в которые можно попасть отдельно
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum HitPart {
+	CANOPY, BODY
+}
+
+impl HitPart {
+	// имя части в манифесте атласа
+	pub fn from_name(name: &str) -> Option<HitPart> {
+		match name {
+			"canopy" => Some(HitPart::CANOPY),
+			"body" => Some(HitPart::BODY),
+			_ => None
+		}
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct HitRegion {
+	pub part: HitPart,
+	pub rect: PlainRect
+}
+
+pub struct HitMask {
+	pub w: u32,
+	pub h: u32,
+	pub bits: Vec<bool>,
+	pub regions: Vec<HitRegion>
+}
+
+impl HitMask {
+	pub fn from_image(img: &Image, rect: PlainRect, regions: &[HitRegion]) -> Self {
+		let mut bits = Vec::with_capacity((rect.w * rect.h) as usize);
+		for y in 0..rect.h as usize {
+			let row = (rect.y as usize + y) * img.stride;
+			for x in 0..rect.w as usize {
+				let pos = row + (rect.x as usize + x) * img.bpp;
+				// без альфа-канала спрайт считается сплошным
+				bits.push(img.bpp < 4 || img.data[pos + 3] >= 128);
+			}
+		}
+		HitMask { w: rect.w, h: rect.h, bits: bits, regions: regions.to_vec() }
+	}
+
+	// зеркальная по горизонтали копия, для отражённых спрайтов
+	pub fn mirrored(&self) -> Self {
+		let mut bits = Vec::with_capacity(self.bits.len());
+		for y in 0..self.h as usize {
+			let row = y * self.w as usize;
+			for x in (0..self.w as usize).rev() {
+				bits.push(self.bits[row + x]);
+			}
+		}
+		let regions = self.regions.iter().map(|region| {
+			let mut rect = region.rect;
+			rect.x = self.w as i32 - rect.x - rect.w as i32;
+			HitRegion { part: region.part, rect: rect }
+		}).collect();
+		HitMask { w: self.w, h: self.h, bits: bits, regions: regions }
+	}
+
+	pub fn is_solid(&self, x: i32, y: i32) -> bool {
+		if x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32 {
+			return false;
+		}
+		self.bits[y as usize * self.w as usize + x as usize]
+	}
+
+	pub fn region_at(&self, x: i32, y: i32) -> Option<HitPart> {
+		for region in self.regions.iter() {
+			let r = region.rect;
+			if x >= r.x && y >= r.y && x < r.x + r.w as i32 && y < r.y + r.h as i32 {
+				return Some(region.part);
+			}
+		}
+		None
+	}
+}
+
+// маски по прямоугольнику спрайта в атласе и признаку отражения
+pub struct HitMaskStore {
+	masks: Vec<(PlainRect, bool, HitMask)>
+}
+
+impl Default for HitMaskStore {
+	fn default() -> Self {
+		HitMaskStore::new()
+	}
+}
+
+impl HitMaskStore {
+	pub fn new() -> Self {
+		HitMaskStore { masks: Vec::new() }
+	}
+
+	// маски для спрайтов атласа с флагом mask; маска покрывает hitbox
+	pub fn create(img: &Image, atlas: &Atlas) -> Self {
+		let mut store = HitMaskStore::new();
+		for sprite in atlas.sprites.iter().filter(|s| s.mask) {
+			let hitbox = PlainRect {
+				x: sprite.rect.x + sprite.hitbox.x,
+				y: sprite.rect.y + sprite.hitbox.y,
+				w: sprite.hitbox.w,
+				h: sprite.hitbox.h
+			};
+			store.add(img, sprite.rect, hitbox, &sprite.regions);
+		}
+		store
+	}
+
+	// key - прямоугольник спрайта в атласе, rect - область маски;
+	// отражённая копия добавляется сразу
+	pub fn add(
+		&mut self, img: &Image, key: PlainRect, rect: PlainRect, regions: &[HitRegion]
+	) -> usize {
+		let mask = HitMask::from_image(img, rect, regions);
+		self.masks.push((key, true, mask.mirrored()));
+		self.masks.push((key, false, mask));
+		self.masks.len() - 1
+	}
+
+	pub fn find(&self, rect: PlainRect, flip_x: bool) -> Option<usize> {
+		self.masks.iter().position(|(r, f, _)| *r == rect && *f == flip_x)
+	}
+
+	pub fn get(&self, index: usize) -> &HitMask {
+		&self.masks[index].2
+	}
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum BoundsPolicy {
+	DESPAWN,	// удалить молча
+	CLAMP,		// удерживать внутри
+	WRAP,		// появиться с другой стороны
+	EVENT		// сообщить решателю (OFFSCREEN), если вышел через edges
+}
+
+// стороны мира, набор флагов
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Edges(pub u32);
+
+impl Edges {
+	pub const NONE: Edges = Edges(0);
+	pub const LEFT: Edges = Edges(1 << 0);
+	pub const TOP: Edges = Edges(1 << 1);
+	pub const RIGHT: Edges = Edges(1 << 2);
+	pub const BOTTOM: Edges = Edges(1 << 3);
+	pub const ALL: Edges = Edges(0xf);
+
+	pub fn contains(self, other: Edges) -> bool {
+		self.0 & other.0 == other.0
+	}
+}
+
+impl BitOr for Edges {
+	type Output = Edges;
+
+	fn bitor(self, other: Edges) -> Edges {
+		Edges(self.0 | other.0)
+	}
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BoundsStatus {
+	INSIDE, DESPAWN, EVENT
+}
+
+#[derive(Copy, Clone)]
+pub struct BoundsRule {
+	pub policy: BoundsPolicy,
+	pub margin: i32,
+	pub edges: Edges	// для EVENT; выход через другую сторону - DESPAWN
+}
+
+// границы игрового мира, не зависящие от размеров окна
+pub struct WorldBounds {
+	pub rect: PlainRect,
+	rules: [BoundsRule; GMO_TYPE_CNT]
+}
+
+impl WorldBounds {
+	pub fn new(rect: PlainRect) -> Self {
+		WorldBounds {
+			rect: rect,
+			rules: [
+				BoundsRule { policy: BoundsPolicy::DESPAWN, margin: 0, edges: Edges::ALL };
+				GMO_TYPE_CNT
+			]
+		}
+	}
+
+	pub fn create(rect: PlainRect) -> Self {
+		let mut bounds = WorldBounds::new(rect);
+		bounds.set(GmoType::GUN, BoundsPolicy::CLAMP, 0);
+		bounds.set(GmoType::CARRIER, BoundsPolicy::EVENT, 0);
+		bounds.set(GmoType::BOMBER, BoundsPolicy::EVENT, 0);
+		// разбивается только упавший на землю; сброшенный за боковым краем
+		// экрана просто исчезает
+		bounds.set(GmoType::FALLING, BoundsPolicy::EVENT, 0);
+		bounds.set_edges(GmoType::FALLING, Edges::BOTTOM);
+		bounds.set(GmoType::CHUTE, BoundsPolicy::EVENT, 0);
+		bounds.set(GmoType::STANDING, BoundsPolicy::CLAMP, 0);
+		bounds
+	}
+
+	pub fn set(&mut self, gmo_type: GmoType, policy: BoundsPolicy, margin: i32) {
+		self.rules[gmo_type as usize] = BoundsRule { policy: policy, margin: margin, edges: Edges::ALL };
+	}
+
+	pub fn set_edges(&mut self, gmo_type: GmoType, edges: Edges) {
+		self.rules[gmo_type as usize].edges = edges;
+	}
+
+	pub fn get(&self, gmo_type: GmoType) -> BoundsRule {
+		self.rules[gmo_type as usize]
+	}
+
+	// CLAMP держит объект целиком внутри границы плюс margin; для остальных
+	// политик объект вне мира, когда он целиком вышел за неё
+	pub fn apply(&self, gmo_type: GmoType, data: &mut GmoData) -> BoundsStatus {
+		let rule = self.rules[gmo_type as usize];
+		let left = (self.rect.x - rule.margin) as f32;
+		let top = (self.rect.y - rule.margin) as f32;
+		let right = (self.rect.x + self.rect.w as i32 + rule.margin) as f32;
+		let bottom = (self.rect.y + self.rect.h as i32 + rule.margin) as f32;
+		let w = data.w as f32;
+		let h = data.h as f32;
+
+		if rule.policy == BoundsPolicy::CLAMP {
+			data.x = data.x.min(right - w).max(left);
+			data.y = data.y.min(bottom - h).max(top);
+			return BoundsStatus::INSIDE;
+		}
+
+		// сторона, через которую объект вышел; низ важнее боков
+		let edge = if data.y >= bottom {
+			Edges::BOTTOM
+		} else if data.y + h < top {
+			Edges::TOP
+		} else if data.x + w < left {
+			Edges::LEFT
+		} else if data.x >= right {
+			Edges::RIGHT
+		} else {
+			return BoundsStatus::INSIDE;
+		};
+
+		match rule.policy {
+			BoundsPolicy::EVENT if rule.edges.contains(edge) => BoundsStatus::EVENT,
+			BoundsPolicy::WRAP => {
+				let span_x = right - left + w;
+				let span_y = bottom - top + h;
+				data.x = left - w + (data.x - left + w).rem_euclid(span_x);
+				data.y = top - h + (data.y - top + h).rem_euclid(span_y);
+				BoundsStatus::INSIDE
+			},
+			_ => BoundsStatus::DESPAWN
+		}
+	}
+}
+
+// результат попиксельной проверки: части спрайтов, которые соприкоснулись
+struct MaskHit {
+	src_part: Option<HitPart>,
+	dst_part: Option<HitPart>
+}
+
+// интервал времени, в течение которого проекции пересекаются по одной оси
+fn sweep_axis(a: f32, aw: f32, b: f32, bw: f32, v: f32) -> Option<(f32, f32)> {
+	if v == 0.0 {
+		if a + aw >= b && a < b + bw {
+			return Some((f32::NEG_INFINITY, f32::INFINITY));
+		}
+		return None;
+	}
+	let t0 = (b - aw - a) / v;
+	let t1 = (b + bw - a) / v;
+	if t0 > t1 {
+		return Some((t1, t0));
+	}
+	Some((t0, t1))
 }
 
 pub struct Collider {}
 
 impl Collider {
 	pub fn check(
-		&self, rect: PlainRect, pantry_gmo: &mut Pantry<GameObject>,
-		vec_collide: &mut Vec<CollidePair>
+		&self, pantry_gmo: &mut Pantry<GameObject>,
+		vec_collide: &mut Vec<CollidePair>, hit_masks: &HitMaskStore
 	) {
 
 		if pantry_gmo.len() == 0 {
@@ -43,39 +439,40 @@ impl Collider {
 		let mut i = pantry_gmo.first_index();
 		loop {
 			let mask = pantry_gmo.get(i).collide_mask;
-			if mask.dst != CollideGroup::NONE || mask.src != CollideGroup::NONE
-			{
+			if !mask.is_empty() {
 				let src_data = pantry_gmo.get(i).data;
-
-				if src_data.x as u32 >= rect.w	|| src_data.y as u32 >= rect.h
-					|| src_data.x < rect.x || src_data.y < rect.y
-				{
-					vec_collide.push(
-						CollidePair {
-							src_index: i, dst_index: i,
-							status: CollideStatus::OFFSCREEN
-						}
-					);
-				} else {
-					let mut j = i;
-					while !pantry_gmo.is_last_index(j) {
-						j = pantry_gmo.next_index(j);
-						let gmo = pantry_gmo.get(j);
-						if mask.dst != CollideGroup::NONE && mask.dst == gmo.collide_mask.src
-							|| mask.src != CollideGroup::NONE && mask.src == gmo.collide_mask.dst
-						{
-							let dst_data = gmo.data;
-							if src_data.x >= dst_data.x + (dst_data.w as i32)
-								|| src_data.y >= dst_data.y + (dst_data.h as i32)
-								|| src_data.x + (src_data.w as i32) < dst_data.x
-								|| src_data.y + (src_data.h as i32) < dst_data.y
-							{
-								continue;
+				let mut j = i;
+				while !pantry_gmo.is_last_index(j) {
+					j = pantry_gmo.next_index(j);
+					let gmo = pantry_gmo.get(j);
+					if !mask.test(&gmo.collide_mask) {
+						continue;
+					}
+					let src = pantry_gmo.get(i);
+					let toi = if mask.fast || gmo.collide_mask.fast {
+						self.sweep(src, gmo)
+					} else if self.overlap(&src_data, &gmo.data) {
+						Some(1.0)
+					} else {
+						None
+					};
+					if let Some(toi) = toi {
+						let mut pair = CollidePair {
+							src_index: i, dst_index: j,
+							status: CollideStatus::COLLIDE, toi: toi,
+							src_part: None, dst_part: None
+						};
+						if src.hit_mask.is_some() || gmo.hit_mask.is_some() {
+							match self.refine(src, gmo, toi, hit_masks) {
+								Some((t, hit)) => {
+									pair.toi = t;
+									pair.src_part = hit.src_part;
+									pair.dst_part = hit.dst_part;
+								},
+								None => continue
 							}
-							vec_collide.push(
-								CollidePair { src_index: i, dst_index: j, status: CollideStatus::COLLIDE }
-							);
 						}
+						vec_collide.push(pair);
 					}
 				}
 			}
@@ -85,137 +482,437 @@ impl Collider {
 			i = pantry_gmo.next_index(i);
 		}
 	}
+
+	pub fn overlap(&self, src_data: &GmoData, dst_data: &GmoData) -> bool {
+		!(src_data.x >= dst_data.x + dst_data.w as f32
+			|| src_data.y >= dst_data.y + dst_data.h as f32
+			|| src_data.x + (src_data.w as f32) < dst_data.x
+			|| src_data.y + (src_data.h as f32) < dst_data.y)
+	}
+
+	// swept AABB: src движется относительно dst от prev к текущей позиции,
+	// возвращает долю кадра, на которой прямоугольники впервые касаются
+	pub fn sweep(&self, src: &GameObject, dst: &GameObject) -> Option<f32> {
+		let vx = (src.data.x - src.prev_x) - (dst.data.x - dst.prev_x);
+		let vy = (src.data.y - src.prev_y) - (dst.data.y - dst.prev_y);
+
+		let (tx0, tx1) = sweep_axis(
+			src.prev_x, src.data.w as f32, dst.prev_x, dst.data.w as f32, vx
+		)?;
+		let (ty0, ty1) = sweep_axis(
+			src.prev_y, src.data.h as f32, dst.prev_y, dst.data.h as f32, vy
+		)?;
+
+		let t_enter = tx0.max(ty0);
+		let t_exit = tx1.min(ty1);
+		if t_enter > t_exit || t_enter > 1.0 || t_exit < 0.0 {
+			return None;
+		}
+		Some(t_enter.max(0.0))
+	}
+
+	// уточнение столкновения по маскам: проходим остаток кадра с шагом
+	// в пиксель и ищем первое пересечение непрозрачных пикселей
+	fn refine(
+		&self, src: &GameObject, dst: &GameObject, toi: f32, hit_masks: &HitMaskStore
+	) -> Option<(f32, MaskHit)> {
+		let src_dx = src.data.x - src.prev_x;
+		let src_dy = src.data.y - src.prev_y;
+		let dst_dx = dst.data.x - dst.prev_x;
+		let dst_dy = dst.data.y - dst.prev_y;
+		let steps = (src_dx - dst_dx).abs().max((src_dy - dst_dy).abs()).ceil().max(1.0) as i32;
+
+		// маски сравниваются в целых пикселях
+		for k in 0..=steps {
+			let t = if toi >= 1.0 { 1.0 } else { toi + (1.0 - toi) * k as f32 / steps as f32 };
+			let sx = (src.prev_x + src_dx * t).round() as i32;
+			let sy = (src.prev_y + src_dy * t).round() as i32;
+			let dx = (dst.prev_x + dst_dx * t).round() as i32;
+			let dy = (dst.prev_y + dst_dy * t).round() as i32;
+			if let Some(hit) = self.mask_overlap(src, (sx, sy), dst, (dx, dy), hit_masks) {
+				return Some((t, hit));
+			}
+			if toi >= 1.0 {
+				break;
+			}
+		}
+		None
+	}
+
+	// src_pos и dst_pos - левые верхние углы хитбоксов в целых пикселях
+	fn mask_overlap(
+		&self, src: &GameObject, src_pos: (i32, i32),
+		dst: &GameObject, dst_pos: (i32, i32), hit_masks: &HitMaskStore
+	) -> Option<MaskHit> {
+		let (sx, sy) = src_pos;
+		let (dx, dy) = dst_pos;
+		let x0 = sx.max(dx);
+		let y0 = sy.max(dy);
+		let x1 = (sx + src.data.w as i32).min(dx + dst.data.w as i32);
+		let y1 = (sy + src.data.h).min(dy + dst.data.h);
+
+		let src_mask = src.hit_mask.map(|index| hit_masks.get(index));
+		let dst_mask = dst.hit_mask.map(|index| hit_masks.get(index));
+
+		for y in y0..y1 {
+			for x in x0..x1 {
+				let src_solid = src_mask.is_none_or(|m| m.is_solid(x - sx, y - sy));
+				let dst_solid = dst_mask.is_none_or(|m| m.is_solid(x - dx, y - dy));
+				if src_solid && dst_solid {
+					return Some(
+						MaskHit {
+							src_part: src_mask.and_then(|m| m.region_at(x - sx, y - sy)),
+							dst_part: dst_mask.and_then(|m| m.region_at(x - dx, y - dy))
+						}
+					);
+				}
+			}
+		}
+		None
+	}
 }
 
-pub struct SolverEvents {
-	pub aerial_offscreen: u8,
-	pub shot_carriers: u8,
-	pub shot_bombers: u8,
-	pub shot_chutes: u8,
-	pub shot_falling: u8,
-	pub shot_bombs: u8,
-	pub blown_guns: u8,
-	pub landed_left: u8,
-	pub landed_right: u8,
-	pub killed_left: u8,
-	pub killed_right: u8
+// исход столкновения a с b; a == GmoType::NONE означает выход b за границы мира
+#[derive(Copy, Clone)]
+pub struct CollideRule {
+	pub a: GmoType,
+	pub b: GmoType,
+	pub b_part: Option<HitPart>,	// None - любая часть b
+	pub destroy_a: bool,
+	pub destroy_b: bool,
+	pub transform_b: GmoType,			// NONE - не превращать
+	pub signal_b: Option<BhvSignal>,	// сигнал автомату поведения b
+	pub debris: GmoType,				// NONE - без обломков
+	pub debris_cnt: u32,
+	pub explode_b: bool,				// взрыв на месте b
+	pub event: Option<GameEventKind>
 }
 
-impl SolverEvents {
-	pub fn new() -> Self {
-		SolverEvents {
-			aerial_offscreen: 0,
-			shot_carriers: 0,
-			shot_bombers: 0,
-			shot_chutes: 0,
-			shot_falling: 0,
-			shot_bombs: 0,
-			blown_guns: 0,
-			landed_left: 0,
-			landed_right: 0,
-			killed_left: 0,
-			killed_right: 0
+impl CollideRule {
+	pub fn new(a: GmoType, b: GmoType) -> Self {
+		CollideRule {
+			a: a,
+			b: b,
+			b_part: None,
+			destroy_a: false,
+			destroy_b: false,
+			transform_b: GmoType::NONE,
+			signal_b: None,
+			debris: GmoType::NONE,
+			debris_cnt: 0,
+			explode_b: false,
+			event: None
 		}
 	}
+
+	pub fn offscreen(b: GmoType) -> Self {
+		CollideRule::new(GmoType::NONE, b)
+	}
+
+	pub fn part(mut self, part: HitPart) -> Self {
+		self.b_part = Some(part);
+		self
+	}
+
+	pub fn destroy_a(mut self) -> Self {
+		self.destroy_a = true;
+		self
+	}
+
+	pub fn destroy_b(mut self) -> Self {
+		self.destroy_b = true;
+		self
+	}
+
+	pub fn transform_b(mut self, gmo_type: GmoType) -> Self {
+		self.transform_b = gmo_type;
+		self
+	}
+
+	pub fn signal_b(mut self, signal: BhvSignal) -> Self {
+		self.signal_b = Some(signal);
+		self
+	}
+
+	pub fn debris(mut self, gmo_type: GmoType, cnt: u32) -> Self {
+		self.debris = gmo_type;
+		self.debris_cnt = cnt;
+		self
+	}
+
+	pub fn explode_b(mut self) -> Self {
+		self.explode_b = true;
+		self
+	}
+
+	pub fn event(mut self, kind: GameEventKind) -> Self {
+		self.event = Some(kind);
+		self
+	}
+
+	fn matches(&self, a: GmoType, b: GmoType, b_part: Option<HitPart>) -> bool {
+		self.a == a && self.b == b && (self.b_part.is_none() || self.b_part == b_part)
+	}
+}
+
+pub struct Solver {
+	rules: Vec<CollideRule>
 }
 
-pub struct Solver {}
+impl Default for Solver {
+	fn default() -> Self {
+		Solver::new()
+	}
+}
 
 impl Solver {
+	pub fn new() -> Self {
+		Solver { rules: Vec::new() }
+	}
+
+	// вся таблица исходов столкновений игры
+	pub fn create() -> Self {
+		let mut solver = Solver::new();
+		solver.add_rule(
+			CollideRule::new(GmoType::SHOT, GmoType::CARRIER)
+				.destroy_a().destroy_b().explode_b().event(GameEventKind::CarrierDestroyed)
+		);
+		solver.add_rule(
+			CollideRule::new(GmoType::SHOT, GmoType::BOMBER)
+				.destroy_a().destroy_b().explode_b().event(GameEventKind::BomberDestroyed)
+		);
+		// падающий и стоящий парашютист гибнут без очков
+		solver.add_rule(
+			CollideRule::new(GmoType::SHOT, GmoType::FALLING).destroy_a().destroy_b()
+		);
+		solver.add_rule(
+			CollideRule::new(GmoType::SHOT, GmoType::STANDING).destroy_a().destroy_b()
+		);
+		// купол пробит - парашютист падает, пуля летит дальше
+		solver.add_rule(
+			CollideRule::new(GmoType::SHOT, GmoType::CHUTE).part(HitPart::CANOPY)
+				.signal_b(BhvSignal::HIT).event(GameEventKind::ChuteShot)
+		);
+		solver.add_rule(
+			CollideRule::new(GmoType::SHOT, GmoType::CHUTE).part(HitPart::BODY)
+				.destroy_a().destroy_b().event(GameEventKind::TrooperShot)
+		);
+		solver.add_rule(
+			CollideRule::offscreen(GmoType::CARRIER)
+				.destroy_b().event(GameEventKind::AerialEscaped)
+		);
+		solver.add_rule(
+			CollideRule::offscreen(GmoType::BOMBER)
+				.destroy_b().event(GameEventKind::AerialEscaped)
+		);
+		solver.add_rule(
+			CollideRule::offscreen(GmoType::FALLING)
+				.destroy_b().debris(GmoType::SPLOSH, 6).event(GameEventKind::TrooperSplat)
+		);
+		solver
+	}
+
+	pub fn add_rule(&mut self, rule: CollideRule) {
+		self.rules.push(rule);
+	}
+
+	// правило и индексы сторон a, b в pantry
+	fn find_rule(
+		&self, pair: &CollidePair, pantry_gmo: &Pantry<GameObject>
+	) -> Option<(CollideRule, usize, usize)> {
+		let src_type = pantry_gmo.get(pair.src_index).gmo_type;
+		if pair.status == CollideStatus::OFFSCREEN {
+			return match self.rules.iter().find(|r| r.matches(GmoType::NONE, src_type, None)) {
+				Some(rule) => Some((*rule, pair.src_index, pair.src_index)),
+				// объект, покинувший мир без правила, просто удаляется
+				None => Some((CollideRule::offscreen(src_type).destroy_b(), pair.src_index, pair.src_index))
+			};
+		}
+		let dst_type = pantry_gmo.get(pair.dst_index).gmo_type;
+		for rule in self.rules.iter() {
+			if rule.matches(src_type, dst_type, pair.dst_part) {
+				return Some((*rule, pair.src_index, pair.dst_index));
+			}
+			if rule.matches(dst_type, src_type, pair.src_part) {
+				return Some((*rule, pair.dst_index, pair.src_index));
+			}
+		}
+		None
+	}
+
 	pub fn solve(
-		&self, pantry_gmo: &mut Pantry<GameObject>,
-		vec_collide: &mut Vec<CollidePair>,
+		&self, pantry_gmo: &Pantry<GameObject>,
+		vec_collide: &mut [CollidePair],
 		ctx: &mut Context
-	) -> SolverEvents {
-
-		let mut sevt = SolverEvents::new();
+	) {
+		// раньше обрабатываются столкновения, случившиеся раньше внутри кадра
+		vec_collide.sort_by(|a, b| a.toi.total_cmp(&b.toi));
 
-		for i in 0..vec_collide.len() {
-			let status = vec_collide[i].status;
-			if status == CollideStatus::NONE {
+		for pair in vec_collide.iter() {
+			if pair.status == CollideStatus::NONE {
 				continue;
 			}
-			let mut should_delete = true;
-			let src_index = vec_collide[i].src_index;
-			let src_type = pantry_gmo.get(src_index).gmo_type;
-
-			if status == CollideStatus::OFFSCREEN {
-				if src_type == GmoType::CARRIER {
-					sevt.aerial_offscreen += 1;
-				} else if src_type == GmoType::FALLING {
-					sevt.shot_chutes += 1;
-					let data = pantry_gmo.get(src_index).data;
-					let gmo_factory = ctx.gmo_factory;
-					for i in 0..6 {
-						let pos = (ctx.rand.randint(0, data.w * 2) - data.w / 2) as i32;
-						let speed = -(ctx.rand.randint(3, 12) as i32);
-						let gmo = gmo_factory.spawn_splosh(ctx, data.x + pos, data.y, speed);
-						let sto = ctx.sto_factory.spawn_splosh(data.x, data.y);
-						ctx.vec_gmo_new.push(
-							GmoNew { sto: sto, gmo: gmo }
-						);
-					}
-				}
-			} else {
-				let dst_index = vec_collide[i].dst_index;
-				let dst_type = pantry_gmo.get(dst_index).gmo_type;
-				if src_type == GmoType::CARRIER || dst_type == GmoType::CARRIER {
-					sevt.shot_carriers += 1;
-				} else if src_type == GmoType::BOMBER || dst_type == GmoType::BOMBER {
-					sevt.shot_bombers += 1;
-				} else if src_type == GmoType::CHUTE || dst_type == GmoType::CHUTE {
-					let mut index_chute = dst_index;
-					let mut index_shot = src_index;
-					if src_type == GmoType::CHUTE {
-	 					index_chute = src_index;
-						index_shot = dst_index;
-					}
-					let data_chute = pantry_gmo.get(index_chute).data;
-					let data_shot = pantry_gmo.get(index_shot).data;
-					if data_shot.y - data_chute.y < 15 {
-						// попадание в купол
-						sevt.shot_chutes += 1;
-						let gmo_factory = ctx.gmo_factory;
-						let mut spawned = gmo_factory.spawn_falling(ctx, data_chute.x, data_chute.y);
-						spawned.data.x += ((data_chute.w - spawned.data.w) >> 1) as i32;
-						spawned.data.y += (data_chute.h - spawned.data.h) as i32;
-
-						let sto = ctx.sto_factory.spawn_falling(spawned.data.x, spawned.data.y);
-						let gmo_chute = pantry_gmo.get_mut(index_chute);
-						// in-place
-						gmo_chute.update_from(ctx, &spawned, sto);
-						should_delete = false;
-					} else if data_shot.y > 25
-						&& data_shot.x - data_chute.x > 12
-						&& data_chute.x + data_chute.w as i32 - data_shot.x > 12 {
-						// попадание в парашютиста
-						sevt.shot_chutes += 1;
-					} else {
-						should_delete = false;
-					}
+			let (rule, index_a, index_b) = match self.find_rule(pair, pantry_gmo) {
+				Some(found) => found,
+				None => continue
+			};
+			// одна сторона уже уничтожена или заменена раньше в этом кадре
+			if ctx.commands.is_pending(index_a) || ctx.commands.is_pending(index_b) {
+				continue;
+			}
+
+			let data_b = pantry_gmo.get(index_b).data;
+			let gmo_factory = ctx.gmo_factory;
+			for _ in 0..rule.debris_cnt {
+				if let Some(new) = gmo_factory.spawn_debris(ctx, rule.debris, &data_b) {
+					ctx.commands.spawn(new);
 				}
+			}
+			if rule.explode_b {
+				spawn_explosion(ctx, &data_b);
+			}
+			if let Some(kind) = rule.event {
+				let gmo_type = pantry_gmo.get(index_b).gmo_type;
+				ctx.events.push(kind, gmo_type, data_b.x, data_b.y);
+			}
 
-				if should_delete {
-					for j in i + 1..vec_collide.len() {
-						let src_index2 = vec_collide[j].src_index;
-						let dst_index2 = vec_collide[j].dst_index;
-						if src_index == src_index2 || src_index == dst_index2
-							|| dst_index == src_index2 || dst_index == dst_index2
-						{
-							vec_collide[j].status = CollideStatus::NONE;
-						}
-					}
-					pantry_gmo.get_mut(dst_index).free(ctx);
-					pantry_gmo.free(dst_index);
+			let mut transformed = false;
+			if rule.transform_b != GmoType::NONE {
+				if let Some(mut new) = gmo_factory.spawn_type(ctx, rule.transform_b, data_b.x, data_b.y) {
+					// новый объект встаёт на место старого: по центру и по нижнему краю
+					new.gmo.data.x += (data_b.w as i32 - new.gmo.data.w as i32) as f32 / 2.0;
+					new.gmo.data.y += (data_b.h - new.gmo.data.h) as f32;
+					let (x, y) = new.gmo.sprite_pos();
+					new.sto.x = x;
+					new.sto.y = y;
+					ctx.commands.transform(index_b, new);
+					transformed = true;
 				}
 			}
 
-			if should_delete {
-				pantry_gmo.get(src_index).free(ctx);
-				pantry_gmo.free(src_index);
+			if let Some(signal) = rule.signal_b {
+				ctx.commands.signal(index_b, signal);
+			}
+
+			if rule.destroy_a && index_a != index_b {
+				ctx.commands.despawn(index_a);
+			}
+			if rule.destroy_b && !transformed {
+				ctx.commands.despawn(index_b);
 			}
 		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::behaviour::BhvMachine;
+	use crate::storage::BhvDataSet;
+
+	static BHV_NONE: BhvMachine = BhvMachine { states: &[] };
+
+	fn gmo(prev: (f32, f32), pos: (f32, f32), w: u32, h: i32) -> GameObject {
+		GameObject {
+			gmo_type: GmoType::NONE,
+			sto_index: 0,
+			data: GmoData { x: pos.0, y: pos.1, w: w, h: h },
+			collide_mask: CollideMask::NONE,
+			prev_x: prev.0,
+			prev_y: prev.1,
+			hit_mask: None,
+			hit_dx: 0,
+			hit_dy: 0,
+			fade_frames: 0,
+			bhv: &BHV_NONE,
+			bhv_state: 0,
+			bhvd: BhvDataSet::new(&[])
+		}
+	}
+
+	fn bounds() -> WorldBounds {
+		WorldBounds::new(PlainRect { x: 0, y: 0, w: 100, h: 100 })
+	}
+
+	#[test]
+	fn bounds_despawn() {
+		let mut world = bounds();
+		world.set(GmoType::SHOT, BoundsPolicy::DESPAWN, 0);
+		let mut data = GmoData { x: 95.0, y: 50.0, w: 10, h: 10 };
+		assert_eq!(world.apply(GmoType::SHOT, &mut data), BoundsStatus::INSIDE);
+		let mut data = GmoData { x: 101.0, y: 50.0, w: 10, h: 10 };
+		assert_eq!(world.apply(GmoType::SHOT, &mut data), BoundsStatus::DESPAWN);
+		// margin отодвигает границу
+		world.set(GmoType::SHOT, BoundsPolicy::DESPAWN, 5);
+		assert_eq!(world.apply(GmoType::SHOT, &mut data), BoundsStatus::INSIDE);
+	}
+
+	#[test]
+	fn bounds_clamp_keeps_inside() {
+		let mut world = bounds();
+		world.set(GmoType::GUN, BoundsPolicy::CLAMP, 0);
+		// частично за правым краем - прижимается, не дожидаясь полного выхода
+		let mut data = GmoData { x: 95.0, y: -3.0, w: 10, h: 10 };
+		assert_eq!(world.apply(GmoType::GUN, &mut data), BoundsStatus::INSIDE);
+		assert_eq!((data.x, data.y), (90.0, 0.0));
+		let mut data = GmoData { x: -40.0, y: 130.0, w: 10, h: 10 };
+		world.apply(GmoType::GUN, &mut data);
+		assert_eq!((data.x, data.y), (0.0, 90.0));
+	}
+
+	#[test]
+	fn bounds_wrap() {
+		let mut world = bounds();
+		world.set(GmoType::SHOT, BoundsPolicy::WRAP, 0);
+		let mut data = GmoData { x: 101.0, y: 50.0, w: 10, h: 10 };
+		assert_eq!(world.apply(GmoType::SHOT, &mut data), BoundsStatus::INSIDE);
+		assert_eq!((data.x, data.y), (-9.0, 50.0));
+	}
+
+	#[test]
+	fn bounds_event_only_through_edges() {
+		let mut world = bounds();
+		world.set(GmoType::FALLING, BoundsPolicy::EVENT, 0);
+		world.set_edges(GmoType::FALLING, Edges::BOTTOM);
+		let mut data = GmoData { x: 50.0, y: 100.0, w: 10, h: 10 };
+		assert_eq!(world.apply(GmoType::FALLING, &mut data), BoundsStatus::EVENT);
+		let mut data = GmoData { x: -31.0, y: 70.0, w: 10, h: 10 };
+		assert_eq!(world.apply(GmoType::FALLING, &mut data), BoundsStatus::DESPAWN);
+		let mut data = GmoData { x: 50.0, y: 70.0, w: 10, h: 10 };
+		assert_eq!(world.apply(GmoType::FALLING, &mut data), BoundsStatus::INSIDE);
+	}
+
+	#[test]
+	fn sweep_axis_interval() {
+		// без движения: пересекаются всегда или никогда
+		assert_eq!(sweep_axis(0.0, 10.0, 5.0, 10.0, 0.0), Some((f32::NEG_INFINITY, f32::INFINITY)));
+		assert_eq!(sweep_axis(0.0, 10.0, 20.0, 10.0, 0.0), None);
+		// a шириной 2 идёт вправо со скоростью 10 к b на 10..15
+		assert_eq!(sweep_axis(0.0, 2.0, 10.0, 5.0, 10.0), Some((0.8, 1.5)));
+		// то же навстречу: интервал упорядочен
+		assert_eq!(sweep_axis(20.0, 2.0, 10.0, 5.0, -10.0), Some((0.5, 1.2)));
+	}
+
+	#[test]
+	fn sweep_catches_tunnelling() {
+		// снаряд 3x3 за кадр пролетает 40 пикселей вверх сквозь солдата высотой 17
+		let collider = Collider {};
+		let shot = gmo((105.0, 100.0), (105.0, 60.0), 3, 3);
+		let trooper = gmo((100.0, 70.0), (100.0, 70.0), 17, 17);
+		assert!(!collider.overlap(&shot.data, &trooper.data));
+		let toi = collider.sweep(&shot, &trooper).unwrap();
+		assert!((toi - 0.325).abs() < 1e-4);
+	}
 
-		sevt
+	#[test]
+	fn sweep_misses_side_and_future() {
+		let collider = Collider {};
+		let trooper = gmo((100.0, 70.0), (100.0, 70.0), 17, 17);
+		// мимо сбоку
+		let shot = gmo((130.0, 100.0), (130.0, 60.0), 3, 3);
+		assert_eq!(collider.sweep(&shot, &trooper), None);
+		// не долетел в этом кадре
+		let shot = gmo((105.0, 140.0), (105.0, 100.0), 3, 3);
+		assert_eq!(collider.sweep(&shot, &trooper), None);
 	}
 }
@@ -0,0 +1,175 @@
+use crate::game::{ GameObject, GmoNew };
+use crate::behaviour::{ BhvSignal, BhvStatus };
+use crate::pantry::Pantry;
+use crate::Context;
+
+// отложенные изменения набора объектов; применяются один раз в конце кадра,
+// поэтому индексы в pantry_gmo остаются действительными весь кадр
+pub struct CommandBuffer {
+	spawns: Vec<GmoNew>,
+	despawns: Vec<usize>,
+	transforms: Vec<(usize, GmoNew)>,
+	signals: Vec<(usize, BhvSignal)>
+}
+
+impl CommandBuffer {
+	pub fn create(capacity: usize) -> Self {
+		CommandBuffer {
+			spawns: Vec::with_capacity(capacity),
+			despawns: Vec::with_capacity(capacity),
+			transforms: Vec::with_capacity(capacity),
+			signals: Vec::with_capacity(capacity)
+		}
+	}
+
+	pub fn spawn(&mut self, new: GmoNew) {
+		self.spawns.push(new);
+	}
+
+	pub fn despawn(&mut self, index: usize) {
+		self.despawns.push(index);
+	}
+
+	pub fn transform(&mut self, index: usize, new: GmoNew) {
+		self.transforms.push((index, new));
+	}
+
+	pub fn signal(&mut self, index: usize, signal: BhvSignal) {
+		self.signals.push((index, signal));
+	}
+
+	// объект уже приговорён к удалению или замене в этом кадре
+	pub fn is_pending(&self, index: usize) -> bool {
+		self.despawns.contains(&index)
+			|| self.transforms.iter().any(|(i, _)| *i == index)
+			|| self.signals.iter().any(|(i, _)| *i == index)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.spawns.is_empty() && self.despawns.is_empty()
+			&& self.transforms.is_empty() && self.signals.is_empty()
+	}
+
+	pub fn clear(&mut self) {
+		self.spawns.clear();
+		self.despawns.clear();
+		self.transforms.clear();
+		self.signals.clear();
+	}
+}
+
+pub fn apply(ctx: &mut Context, pantry_gmo: &mut Pantry<GameObject>) {
+	let mut despawns = std::mem::take(&mut ctx.commands.despawns);
+	let mut transforms = std::mem::take(&mut ctx.commands.transforms);
+	let mut spawns = std::mem::take(&mut ctx.commands.spawns);
+	let mut signals = std::mem::take(&mut ctx.commands.signals);
+
+	despawns.sort_unstable();
+	despawns.dedup();
+	for index in despawns.iter() {
+		pantry_gmo.get(*index).free(ctx);
+		pantry_gmo.free(*index);
+	}
+
+	// повторная замена или замена удалённого объекта отменяется
+	let mut transformed: Vec<usize> = Vec::with_capacity(transforms.len());
+	for (index, new) in transforms.drain(..) {
+		if despawns.binary_search(&index).is_ok() || transformed.contains(&index) {
+			new.gmo.release(ctx);
+			continue;
+		}
+		pantry_gmo.get_mut(index).update_from(ctx, &new.gmo, new.sto);
+		transformed.push(index);
+	}
+
+	for (index, signal) in signals.drain(..) {
+		if despawns.binary_search(&index).is_ok() || transformed.contains(&index) {
+			continue;
+		}
+		if pantry_gmo.get_mut(index).signal(ctx, signal) == BhvStatus::END {
+			pantry_gmo.get(index).free(ctx);
+			pantry_gmo.free(index);
+			despawns.push(index);
+		}
+	}
+
+	// при переполнении объект не появляется, но его картинка,
+	// данные поведения и таймеры освобождаются
+	for mut new in spawns.drain(..) {
+		if pantry_gmo.len() == pantry_gmo.capacity {
+			new.gmo.release(ctx);
+			continue;
+		}
+		let sto_index = ctx.stage.add_child(new.sto);
+		if sto_index == ctx.stage.pantry_sto.capacity {
+			new.gmo.release(ctx);
+			continue;
+		}
+		new.gmo.sto_index = sto_index;
+		pantry_gmo.alloc(new.gmo);
+	}
+
+	// вернуть буферы, чтобы не терять выделенную память
+	despawns.clear();
+	ctx.commands.despawns = despawns;
+	ctx.commands.transforms = transforms;
+	ctx.commands.spawns = spawns;
+	ctx.commands.signals = signals;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::behaviour::BhvDataMove;
+
+	fn spawn_shot(ctx: &mut Context) {
+		let gmo_factory = ctx.gmo_factory;
+		let gmo = gmo_factory.spawn_shot(ctx, 10.0, 10.0, BhvDataMove { dx: 0.0, dy: 0.0 }).unwrap();
+		let sto = ctx.sto_factory.spawn_shot(10, 10);
+		ctx.commands.spawn(GmoNew { sto: sto, gmo: gmo });
+	}
+
+	// занятые записи данных поведения всех типов
+	fn storage_used(ctx: &Context) -> usize {
+		ctx.storage.leak_report().iter().map(|(_, indices)| indices.len()).sum()
+	}
+
+	#[test]
+	fn despawn_twice_frees_once() {
+		let mut ctx = Context::for_tests(8);
+		let mut pantry_gmo = Pantry::create(8);
+		spawn_shot(&mut ctx);
+		spawn_shot(&mut ctx);
+		apply(&mut ctx, &mut pantry_gmo);
+		assert_eq!(pantry_gmo.len(), 2);
+
+		let index = pantry_gmo.first_index();
+		ctx.commands.despawn(index);
+		ctx.commands.despawn(index);
+		apply(&mut ctx, &mut pantry_gmo);
+		assert_eq!(pantry_gmo.len(), 1);
+		assert_eq!(ctx.stage.pantry_sto.len(), 1);
+		assert_eq!(storage_used(&ctx), 1);
+		assert!(ctx.commands.is_empty());
+	}
+
+	#[test]
+	fn transform_after_despawn_is_dropped() {
+		let mut ctx = Context::for_tests(8);
+		let mut pantry_gmo = Pantry::create(8);
+		spawn_shot(&mut ctx);
+		apply(&mut ctx, &mut pantry_gmo);
+		let index = pantry_gmo.first_index();
+
+		ctx.commands.despawn(index);
+		let gmo_factory = ctx.gmo_factory;
+		let gmo = gmo_factory.spawn_shot(&mut ctx, 0.0, 0.0, BhvDataMove { dx: 0.0, dy: 0.0 }).unwrap();
+		let sto = ctx.sto_factory.spawn_shot(0, 0);
+		ctx.commands.transform(index, GmoNew { sto: sto, gmo: gmo });
+		apply(&mut ctx, &mut pantry_gmo);
+		// и удалённый объект, и отменённая замена освободили свои данные
+		assert_eq!(pantry_gmo.len(), 0);
+		assert_eq!(ctx.stage.pantry_sto.len(), 0);
+		assert_eq!(storage_used(&ctx), 0);
+	}
+}
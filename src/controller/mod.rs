@@ -1,15 +1,18 @@
 use crate::Context;
 use crate::input::{ Input, InputEvent };
-use crate::collider::{ Collider, CollidePair, Solver };
+use crate::collider::{ Collider, CollidePair, CollideStatus, BoundsStatus, Solver };
 use crate::pantry::{ Pantry };
-use crate::game::{ PlainRect, GmoType, StageObject, GameObject };
+use crate::game::{ GmoType, StageObject, GameObject, GmoNew, Layer };
+use crate::command;
+use crate::factory::{ GUN_W, GUN_H };
+use crate::scheduler::{ self, TimerDelay };
 use crate::behaviour::{ BhvStatus, BhvDataMove, BhvDataGun };
+use crate::event::{ GameEventKind, Score, Statistics };
 use crate::static_drawable::*;
-//use sdl2::keyboard::{Keycode};
 
 #[derive (Copy, Clone, PartialEq)]
 pub enum ControllerEvent {
-	Run, EndTitle, EndGame
+	Run, EndTitle, EndGame, Quit
 }
 
 pub trait Controller
@@ -42,8 +45,12 @@ impl Controller for ControllerTitle
 	fn begin(&mut self, ctx: &mut Context)
 	{
 		ctx.stage.clear();
+		// логотип по центру по горизонтали, на шестой части высоты
+		let logo = ctx.sto_factory.sprites.logo.rect;
 		self.sto_logo_index = ctx.stage.add_child(
-			ctx.sto_factory.spawn_logo(0, 100)
+			ctx.sto_factory.spawn_logo(
+				(ctx.stage.w as i32 - logo.w as i32) / 2, ctx.stage.h as i32 / 6
+			)
 		);
 		self.step = 10;
 		self.cnt = 5;
@@ -51,7 +58,11 @@ impl Controller for ControllerTitle
 
 	fn run(&mut self, ctx: &mut Context, input: &Input) -> ControllerEvent
 	{
-		if input.get_event() == InputEvent::Continue {
+		let evt = input.get_event();
+		if evt == InputEvent::Quit {
+			return ControllerEvent::Quit;
+		}
+		if evt == InputEvent::Continue {
 			return ControllerEvent::EndTitle;
 		}
 
@@ -73,14 +84,15 @@ impl Controller for ControllerTitle
 }
 
 pub struct ControllerGame {
-	sto_shaft_index: usize,
 	collider: Collider,
 	solver: Solver,
 	pantry_gmo: Pantry<GameObject>,
 	vec_collide: Vec<CollidePair>,
 	shoot_cooldown: u8,
 	moving_dir: i8,
-	shooting: bool
+	shooting: bool,
+	score: Score,
+	stats: Statistics
 }
 
 impl ControllerGame
@@ -88,34 +100,52 @@ impl ControllerGame
 	pub fn new(max_obj_cnt: usize) -> Self
 	{
 		Self {
-			sto_shaft_index: 0,
 			collider: Collider {},
-			solver: Solver {},
+			solver: Solver::create(),
 			vec_collide: Vec::with_capacity(max_obj_cnt),
 			pantry_gmo: Pantry::create(max_obj_cnt),
 			shoot_cooldown: 0,
 			moving_dir: 0,
-			shooting: false
+			shooting: false,
+			score: Score::new(),
+			stats: Statistics::new(0.0)
 		}
 	}
 }
 
+impl ControllerGame {
+	// ствол - потомок пушки на сцене; ищется каждый раз, поэтому
+	// не остаётся ссылки на ствол, ушедший со сцены вместе с пушкой
+	fn shaft_index(&self, ctx: &Context) -> Option<usize> {
+		let gun = self.pantry_gmo.used_indices().into_iter()
+			.map(|index| self.pantry_gmo.get(index))
+			.find(|gmo| gmo.gmo_type == GmoType::GUN)?;
+		ctx.stage.children(gun.sto_index).first().copied()
+	}
+}
+
 impl Controller for ControllerGame
 {
 	fn begin(&mut self, ctx: &mut Context)
 	{
 		let gmo_factory = ctx.gmo_factory;
+		// пушка по центру и стоит на нижнем краю сцены
 		let mut gmo_gun = gmo_factory.spawn_gun(
-			ctx, 384, 563,
-			BhvDataGun { wave_type: GmoType::CARRIER, cnt: 10, delay: 30 }
-		);
+			ctx,
+			(ctx.stage.w - GUN_W) as f32 / 2.0, (ctx.stage.h - GUN_H) as f32,
+			BhvDataGun { wave_type: GmoType::CARRIER, cnt: 10 }, TimerDelay::frames(30)
+		).expect("begin: no room for the gun");
 		gmo_gun.sto_index = ctx.stage.add_child(
-			ctx.sto_factory.spawn_gun(gmo_gun.data.x, gmo_gun.data.y)
+			ctx.sto_factory.spawn_gun(gmo_gun.data.x as i32, gmo_gun.data.y as i32)
 		);
-		self.pantry_gmo.alloc(gmo_gun);
-		self.sto_shaft_index = ctx.stage.add_child(
-			StageObject { x: 400, y: 568, angle: 90, drawable: &DR_SHAFT }
+		// ствол крепится к пушке и двигается вместе с ней
+		ctx.stage.add_child_of(
+			gmo_gun.sto_index,
+			StageObject::new(16, 5, 90, &DR_SHAFT).layer(Layer::GROUND).z(1)
 		);
+		self.pantry_gmo.alloc(gmo_gun);
+		self.score = Score::new();
+		self.stats = Statistics::new(ctx.stage.w as f32 / 2.0);
 	}
 
 	fn run(&mut self, ctx: &mut Context, input: &Input) -> ControllerEvent
@@ -126,6 +156,9 @@ impl Controller for ControllerGame
 			return ControllerEvent::EndGame;
 		}
 
+		// таймеры срабатывают до обновления объектов этого кадра
+		scheduler::tick(ctx);
+
 		if evt == InputEvent::MoveLeft {
 			self.moving_dir = 1;
 		} else if evt == InputEvent::MoveRight {
@@ -138,38 +171,50 @@ impl Controller for ControllerGame
 			self.shooting = false;
 		}
 
+		let shaft = self.shaft_index(ctx);
+
 		if self.moving_dir != 0 {
-			let sto = ctx.stage.get_mut(self.sto_shaft_index);
-			sto.angle += 3 * self.moving_dir as i32;
-			if sto.angle > 180 {
-				sto.angle = 180;
-			} else if sto.angle < 0 {
-				sto.angle = 0;
+			if let Some(shaft) = shaft {
+				let sto = ctx.stage.get_mut(shaft);
+				sto.angle += 3 * self.moving_dir as i32;
+				if sto.angle > 180 {
+					sto.angle = 180;
+				} else if sto.angle < 0 {
+					sto.angle = 0;
+				}
 			}
 		}
 
 		if self.shoot_cooldown > 0 {
 			self.shoot_cooldown -= 1;
 		} else if self.shooting {
-			self.shoot_cooldown = 10;
-			let sto = ctx.stage.get(self.sto_shaft_index);
-			let theta: f32 = (sto.angle as f32) * 3.1415926 / 180.0;
-			let cos = theta.cos();
-			let sin = theta.sin();
-			let x = sto.x + (20.0 * cos).round() as i32;
-			let y = sto.y - (20.0 * sin).round() as i32;
-
-			let gmo_factory = ctx.gmo_factory;
-			let mut gmo_shot = gmo_factory.spawn_shot(
-				ctx, x, y,
-				BhvDataMove {
-					dx: (5.0 * cos).round() as i32,
-					dy: -(5.0 * sin).round() as i32
+			if let Some(shaft) = shaft {
+				self.shoot_cooldown = 10;
+				let sto = ctx.stage.world_transform(shaft);
+				let theta: f32 = (sto.angle as f32).to_radians();
+				let cos = theta.cos();
+				let sin = theta.sin();
+				// снаряд летит точно по направлению ствола
+				let x = sto.x as f32 + 20.0 * cos;
+				let y = sto.y as f32 - 20.0 * sin;
+
+				let gmo_factory = ctx.gmo_factory;
+				let gmo_shot = gmo_factory.spawn_shot(
+					ctx, x, y,
+					BhvDataMove {
+						dx: 5.0 * cos,
+						dy: -5.0 * sin
+					}
+				);
+				// нет места - выстрела нет, перезарядка всё равно идёт
+				if let Some(gmo_shot) = gmo_shot {
+					let sto_shot = ctx.sto_factory.spawn_shot(
+						gmo_shot.data.x.round() as i32, gmo_shot.data.y.round() as i32
+					);
+					ctx.events.push(GameEventKind::ShotFired, GmoType::SHOT, x, y);
+					ctx.commands.spawn(GmoNew { sto: sto_shot, gmo: gmo_shot });
 				}
-			);
-			let sto_shot = ctx.sto_factory.spawn_shot(gmo_shot.data.x, gmo_shot.data.y);
-			gmo_shot.sto_index = ctx.stage.add_child(sto_shot);
-			self.pantry_gmo.alloc(gmo_shot);
+			}
 		}
 
 		// process_game_objects(&mut self.pantry_gmo, ctx);
@@ -177,20 +222,34 @@ impl Controller for ControllerGame
 		if self.pantry_gmo.len() > 0 {
 			let mut index = self.pantry_gmo.first_index();
 			loop {
-				// запомнить состояние последнего индекса,
-				// т.к. после pantry_gmo.free() он может измениться
-				let is_last = self.pantry_gmo.is_last_index(index);
+				// удаление отложено до commands::apply, поэтому список не меняется
 				let gmo = self.pantry_gmo.get_mut(index);
-				let status = gmo.bhv.update(ctx, gmo);
+				gmo.prev_x = gmo.data.x;
+				gmo.prev_y = gmo.data.y;
+				let mut status = gmo.update(ctx, index);
+				if status == BhvStatus::OK {
+					// границы мира действуют на все объекты, даже без маски столкновений
+					match ctx.world.apply(gmo.gmo_type, &mut gmo.data) {
+						BoundsStatus::DESPAWN => status = BhvStatus::END,
+						BoundsStatus::EVENT => self.vec_collide.push(
+							CollidePair {
+								src_index: index, dst_index: index,
+								status: CollideStatus::OFFSCREEN, toi: 1.0,
+								src_part: None, dst_part: None
+							}
+						),
+						BoundsStatus::INSIDE => ()
+					}
+				}
 				if status == BhvStatus::END {
-					gmo.free(ctx);
-					self.pantry_gmo.free(index);
+					ctx.commands.despawn(index);
 				} else {
+					let (x, y) = gmo.sprite_pos();
 					let sto = ctx.stage.get_mut(gmo.sto_index);
-					sto.x = gmo.data.x;
-					sto.y = gmo.data.y;
+					sto.x = x;
+					sto.y = y;
 				}
-				if is_last {
+				if self.pantry_gmo.is_last_index(index) {
 					break;
 				}
 				index = self.pantry_gmo.next_index(index);
@@ -198,33 +257,50 @@ impl Controller for ControllerGame
 		}
 
 		self.collider.check(
-			PlainRect { x: 0, y: 0, w: ctx.stage.w, h: ctx.stage.h },
 			&mut self.pantry_gmo,
-			&mut self.vec_collide
+			&mut self.vec_collide,
+			&ctx.hit_masks
 		);
 
 		if self.vec_collide.len() > 0 {
-			let sevt = self.solver.solve(&mut self.pantry_gmo, &mut self.vec_collide, ctx);
-			let score = 10 * sevt.shot_carriers + 5 * sevt.shot_chutes;
-			if score > 0 {
-				//println!("score: {}", score);
-			}
+			self.solver.solve(&self.pantry_gmo, &mut self.vec_collide, ctx);
 			self.vec_collide.clear();
 		}
 
-		while ctx.vec_gmo_new.len() > 0 {
-			let mut new = ctx.vec_gmo_new.pop().unwrap();
-			new.gmo.sto_index = ctx.stage.add_child(new.sto);
-			self.pantry_gmo.alloc(new.gmo);
-		}
+		command::apply(ctx, &mut self.pantry_gmo);
+
+		ctx.events.dispatch(&mut [&mut self.score, &mut self.stats, &mut ctx.stage.camera]);
+		ctx.events.clear();
 
 		ControllerEvent::Run
 	}
 
 	fn end(&mut self, ctx: &mut Context)
 	{
+		// отложенные появления тоже владеют данными поведения
+		command::apply(ctx, &mut self.pantry_gmo);
+		for index in self.pantry_gmo.used_indices() {
+			self.pantry_gmo.get(index).free(ctx);
+		}
+
+		if cfg!(debug_assertions) {
+			for (name, indices) in ctx.storage.leak_report() {
+				eprintln!("leak: {} has {} orphaned entries {:?}", name, indices.len(), indices);
+			}
+		}
+
+		if cfg!(debug_assertions) && ctx.scheduler.active_cnt() > 0 {
+			eprintln!("leak: {} timers still active", ctx.scheduler.active_cnt());
+		}
+
+		ctx.storage.clear();
+		ctx.scheduler.clear();
 		ctx.stage.clear();
+		ctx.events.clear();
 		self.pantry_gmo.clear();
 		self.vec_collide.clear();
+		self.moving_dir = 0;
+		self.shooting = false;
+		self.shoot_cooldown = 0;
 	}
 }
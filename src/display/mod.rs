@@ -0,0 +1,130 @@
+use sdl2::keyboard::Keycode;
+use sdl2::render::WindowCanvas;
+use sdl2::video::FullscreenType;
+
+// как логическое разрешение игры вписывается в окно
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ScaleMode {
+	INTEGER,	// целый масштаб, остаток окна - чёрные поля
+	LETTERBOX,	// максимальный масштаб с сохранением пропорций
+	STRETCH		// растянуть на всё окно, пропорции не сохраняются
+}
+
+impl ScaleMode {
+	pub fn name(&self) -> &'static str {
+		match self {
+			ScaleMode::INTEGER => "integer",
+			ScaleMode::LETTERBOX => "letterbox",
+			ScaleMode::STRETCH => "stretch"
+		}
+	}
+
+	fn next(&self) -> ScaleMode {
+		match self {
+			ScaleMode::INTEGER => ScaleMode::LETTERBOX,
+			ScaleMode::LETTERBOX => ScaleMode::STRETCH,
+			ScaleMode::STRETCH => ScaleMode::INTEGER
+		}
+	}
+}
+
+// игра всегда рисует в логическом разрешении w x h (размер Stage),
+// а SDL масштабирует картинку под текущий размер окна
+pub struct Display {
+	pub w: u32,
+	pub h: u32,
+	mode: ScaleMode,
+	fullscreen: bool
+}
+
+impl Display {
+	pub fn new(w: u32, h: u32) -> Self {
+		Display { w: w, h: h, mode: ScaleMode::LETTERBOX, fullscreen: false }
+	}
+
+	pub fn mode(&self) -> ScaleMode {
+		self.mode
+	}
+
+	pub fn set_mode(&mut self, mode: ScaleMode) {
+		self.mode = mode;
+	}
+
+	pub fn is_fullscreen(&self) -> bool {
+		self.fullscreen
+	}
+
+	pub fn set_fullscreen(&mut self, fullscreen: bool) {
+		self.fullscreen = fullscreen;
+	}
+
+	// вызывается при старте, смене режима и изменении размера окна
+	pub fn apply(&self, canvas: &mut WindowCanvas) {
+		let fullscreen = if self.fullscreen { FullscreenType::Desktop } else { FullscreenType::Off };
+		if canvas.window().fullscreen_state() != fullscreen {
+			if let Err(err) = canvas.window_mut().set_fullscreen(fullscreen) {
+				eprintln!("fullscreen: {}", err);
+			}
+		}
+
+		match self.mode {
+			ScaleMode::INTEGER | ScaleMode::LETTERBOX => {
+				let _ = canvas.set_scale(1.0, 1.0);
+				let _ = canvas.set_logical_size(self.w, self.h);
+				set_integer_scale(canvas, self.mode == ScaleMode::INTEGER);
+			},
+			ScaleMode::STRETCH => {
+				// без логического размера масштаб по осям задаётся отдельно
+				set_integer_scale(canvas, false);
+				let _ = canvas.set_logical_size(0, 0);
+				canvas.set_viewport(None);
+				if let Ok((out_w, out_h)) = canvas.output_size() {
+					let _ = canvas.set_scale(
+						out_w as f32 / self.w as f32, out_h as f32 / self.h as f32
+					);
+				}
+			}
+		}
+	}
+
+	// F10 - следующий режим масштаба, F11 - полный экран
+	pub fn handle_key(&mut self, canvas: &mut WindowCanvas, keycode: Keycode) -> bool {
+		match keycode {
+			Keycode::F10 => self.mode = self.mode.next(),
+			Keycode::F11 => self.fullscreen = !self.fullscreen,
+			_ => return false
+		}
+		self.apply(canvas);
+		true
+	}
+}
+
+pub fn is_display_key(keycode: Keycode) -> bool {
+	matches!(keycode, Keycode::F10 | Keycode::F11)
+}
+
+// в обёртке sdl2 0.34 нет SDL_RenderSetIntegerScale
+fn set_integer_scale(canvas: &mut WindowCanvas, enable: bool) {
+	let value = if enable { sdl2::sys::SDL_bool::SDL_TRUE } else { sdl2::sys::SDL_bool::SDL_FALSE };
+	// SAFETY: canvas.raw() - действующий SDL_Renderer, canvas жив всё время вызова,
+	// а функция только меняет флаг масштаба рендерера и указатель не сохраняет
+	unsafe {
+		sdl2::sys::SDL_RenderSetIntegerScale(canvas.raw(), value);
+	}
+}
+
+// --scale=integer|letterbox|stretch и --fullscreen из командной строки
+pub fn parse_args(display: &mut Display, args: &[String]) {
+	for arg in args.iter() {
+		if arg == "--fullscreen" {
+			display.set_fullscreen(true);
+		} else if let Some(value) = arg.strip_prefix("--scale=") {
+			match value {
+				"integer" => display.set_mode(ScaleMode::INTEGER),
+				"letterbox" => display.set_mode(ScaleMode::LETTERBOX),
+				"stretch" => display.set_mode(ScaleMode::STRETCH),
+				_ => eprintln!("invalid scale mode: {}", value)
+			}
+		}
+	}
+}
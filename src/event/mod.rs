@@ -0,0 +1,131 @@
+use crate::game::GmoType;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum GameEventKind {
+	ShotFired,
+	CarrierDestroyed,
+	BomberDestroyed,
+	AerialEscaped,
+	TrooperDropped,
+	ChuteShot,
+	TrooperShot,
+	TrooperSplat,
+	TrooperLanded,
+	GunHit
+}
+
+pub const GAME_EVENT_KIND_CNT: usize = GameEventKind::GunHit as usize + 1;
+
+#[derive(Copy, Clone)]
+pub struct GameEvent {
+	pub kind: GameEventKind,
+	pub gmo_type: GmoType,
+	pub x: f32,
+	pub y: f32
+}
+
+pub trait EventListener {
+	fn on_event(&mut self, evt: &GameEvent);
+}
+
+// события одного кадра; очищается после рассылки подписчикам
+pub struct EventQueue {
+	events: Vec<GameEvent>
+}
+
+impl EventQueue {
+	pub fn create(capacity: usize) -> Self {
+		EventQueue { events: Vec::with_capacity(capacity) }
+	}
+
+	pub fn push(&mut self, kind: GameEventKind, gmo_type: GmoType, x: f32, y: f32) {
+		self.events.push(GameEvent { kind: kind, gmo_type: gmo_type, x: x, y: y });
+	}
+
+	pub fn len(&self) -> usize {
+		self.events.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.events.is_empty()
+	}
+
+	pub fn iter(&self) -> std::slice::Iter<'_, GameEvent> {
+		self.events.iter()
+	}
+
+	pub fn dispatch(&self, listeners: &mut [&mut dyn EventListener]) {
+		for evt in self.events.iter() {
+			for listener in listeners.iter_mut() {
+				listener.on_event(evt);
+			}
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.events.clear();
+	}
+}
+
+pub struct Score {
+	pub value: u32
+}
+
+impl Score {
+	pub fn new() -> Self {
+		Score { value: 0 }
+	}
+}
+
+impl Default for Score {
+	fn default() -> Self {
+		Score::new()
+	}
+}
+
+impl EventListener for Score {
+	fn on_event(&mut self, evt: &GameEvent) {
+		self.value += match evt.kind {
+			// бомбардировщики очков не дают
+			GameEventKind::CarrierDestroyed => 10,
+			GameEventKind::ChuteShot | GameEventKind::TrooperShot
+				| GameEventKind::TrooperSplat => 5,
+			_ => 0
+		};
+	}
+}
+
+pub struct Statistics {
+	pub counters: [u32; GAME_EVENT_KIND_CNT],
+	pub center_x: f32,
+	pub landed_left: u32,
+	pub landed_right: u32
+}
+
+impl Statistics {
+	pub fn new(center_x: f32) -> Self {
+		Statistics {
+			counters: [0; GAME_EVENT_KIND_CNT],
+			center_x: center_x,
+			landed_left: 0,
+			landed_right: 0
+		}
+	}
+
+	pub fn get(&self, kind: GameEventKind) -> u32 {
+		self.counters[kind as usize]
+	}
+}
+
+impl EventListener for Statistics {
+	fn on_event(&mut self, evt: &GameEvent) {
+		self.counters[evt.kind as usize] += 1;
+		if evt.kind == GameEventKind::TrooperLanded {
+			if evt.x < self.center_x {
+				self.landed_left += 1;
+			} else {
+				self.landed_right += 1;
+			}
+		}
+	}
+}
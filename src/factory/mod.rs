@@ -1,173 +1,520 @@
 use crate::game::*;
 use crate::behaviour::*;
-use crate::collider::*;
+use crate::event::GameEventKind;
+use crate::renderer::{ DrawableAnimation, DrawableExplosion, DrawableGroup, DrawableHandle, DrawableRegistry };
+use sdl2::pixels::Color;
+use crate::atlas::{ Atlas, Sprite };
+use crate::storage::BhvDataSet;
+use crate::scheduler::{ TimerDelay, TimerHandle };
 use crate::{ Context };
 use crate::static_drawable::*;
 
+// спрайты атласа, которые нужны игре; ищутся по имени один раз при запуске
+#[derive(Copy, Clone)]
+pub struct GameSprites {
+	pub logo: &'static Sprite,
+	pub carrier: &'static Sprite,
+	pub chute: &'static Sprite,
+	pub trooper: &'static Sprite,
+	pub falling: &'static Sprite,
+	pub flail: &'static DrawableAnimation,
+	pub walk: &'static DrawableAnimation
+}
+
+impl GameSprites {
+	pub fn resolve(atlas: &'static Atlas) -> Self {
+		GameSprites {
+			logo: atlas.sprite("logo"),
+			carrier: atlas.sprite("carrier"),
+			chute: atlas.sprite("chute"),
+			trooper: atlas.sprite("trooper"),
+			falling: atlas.sprite("falling"),
+			flail: &atlas.anim("flail").drawable,
+			walk: &atlas.anim("walk").drawable
+		}
+	}
+}
+
 #[derive(Copy, Clone)]
 pub struct GmoFactory {
+	pub sprites: GameSprites
 }
 
 #[derive(Copy, Clone)]
 pub struct StoFactory {
+	pub sprites: GameSprites,
+	pub carrier: DrawableHandle		// спрайт carrier с винтами DR_ROTOR
+}
+
+// размер пушки, как у DR_GUN
+pub const GUN_W: u32 = 32;
+pub const GUN_H: u32 = 37;
+
+// состояния BHV_TROOPER
+const TROOPER_DROP: usize = 0;
+const TROOPER_CHUTE: usize = 1;
+const TROOPER_LANDED: usize = 2;
+const TROOPER_WALKING: usize = 3;
+const TROOPER_IDLE: usize = 4;
+const TROOPER_FALLING: usize = 5;
+
+// столько кадров гаснут сбитый вертолёт и брызги после удаления
+const CARRIER_FADE_FRAMES: u32 = 12;
+const SPLOSH_FADE_FRAMES: u32 = 8;
+const HIT_FLASH_FRAMES: u32 = 3;
+const EXPLOSION_FRAMES: u32 = 12;
+// столько кадров парашютист стоит у пушки после удара
+const GUN_HIT_FRAMES: u32 = 10;
+
+static BHV_MOVE: BhvMachine = BhvMachine {
+	states: &[
+		BhvState { components: &[&BehaviourMove {}], transitions: &[] }
+	]
+};
+
+static BHV_FALL: BhvMachine = BhvMachine {
+	states: &[
+		BhvState {
+			components: &[&BehaviourGravity {}, &BehaviourMove {}],
+			transitions: &[]
+		}
+	]
+};
+
+static BHV_GUN: BhvMachine = BhvMachine {
+	states: &[
+		BhvState {
+			components: &[&BehaviourSpawner { spawn: spawn_wave, period: TimerDelay::FRAMES(100, 500) }],
+			transitions: &[]
+		}
+	]
+};
+
+static BHV_CARRIER: BhvMachine = BhvMachine {
+	states: &[
+		BhvState {
+			components: &[
+				&BehaviourSpawner { spawn: drop_trooper, period: TimerDelay::FRAMES(50, 100) },
+				&BehaviourMove {}
+			],
+			transitions: &[]
+		}
+	]
+};
+
+// прыжок -> парашют -> приземление -> идёт к пушке -> бьёт пушку и исчезает;
+// пробитый парашют (HIT) -> падение
+static BHV_TROOPER: BhvMachine = BhvMachine {
+	states: &[
+		BhvState {
+			components: &[&BehaviourMove {}, &BehaviourTimer {}],
+			transitions: &[
+				BhvTransition { signal: BhvSignal::TIMER, to: TROOPER_CHUTE, enter: Some(enter_chute) }
+			]
+		},
+		BhvState {
+			components: &[&BehaviourMove {}, &BehaviourLand {}],
+			transitions: &[
+				BhvTransition { signal: BhvSignal::LANDED, to: TROOPER_LANDED, enter: Some(enter_landed) },
+				BhvTransition { signal: BhvSignal::HIT, to: TROOPER_FALLING, enter: Some(enter_falling) }
+			]
+		},
+		BhvState {
+			components: &[&BehaviourTimer {}],
+			transitions: &[
+				BhvTransition { signal: BhvSignal::TIMER, to: TROOPER_WALKING, enter: Some(enter_walking) }
+			]
+		},
+		BhvState {
+			components: &[&BehaviourMove {}, &BehaviourTimer {}],
+			transitions: &[
+				BhvTransition { signal: BhvSignal::TIMER, to: TROOPER_IDLE, enter: Some(enter_idle) }
+			]
+		},
+		BhvState {
+			components: &[&BehaviourTimer {}],
+			transitions: &[
+				BhvTransition { signal: BhvSignal::TIMER, to: BHV_END, enter: None }
+			]
+		},
+		BhvState { components: &[&BehaviourMove {}], transitions: &[] }
+	]
+};
+
+fn spawn_wave(ctx: &mut Context, gmo: &GameObject) {
+	let bhv_data = ctx.storage.get_mut::<BhvDataGun>(gmo.bhvd.get::<BhvDataGun>());
+	if bhv_data.cnt == 0 {
+		return;
+	}
+	//bhv_data.cnt -= 1;
+	// летящий влево появляется у правого края целиком
+	let mut dx = 1.0;
+	let mut x = 0.0;
+	if ctx.rand.randint(0, 2) == 1 {
+		dx = -1.0;
+		x = (ctx.stage.w - ctx.gmo_factory.sprites.carrier.rect.w) as f32;
+	}
+	let y = (ctx.stage.h / 15) as f32;
+	let gmo_factory = ctx.gmo_factory;
+	let gmc = match gmo_factory.spawn_carrier(ctx, x, y, dx, TimerDelay::FRAMES(10, 20)) {
+		Some(gmc) => gmc,
+		None => return
+	};
+	let (sx, sy) = gmc.sprite_pos();
+	ctx.commands.spawn(
+		GmoNew {
+			sto: ctx.sto_factory.spawn_carrier(sx, sy, dx),
+			gmo: gmc
+		}
+	);
+}
+
+fn drop_trooper(ctx: &mut Context, gmo: &GameObject) {
+	let speed = ctx.storage.get::<BhvDataMove>(gmo.bhvd.get::<BhvDataMove>()).dx;
+	let gmo_factory = ctx.gmo_factory;
+	let mut gmt = match gmo_factory.spawn_trooper(ctx, gmo.data.x, gmo.data.y + 24.0) {
+		Some(gmt) => gmt,
+		None => return
+	};
+	if speed > 0.0 {
+		gmt.data.x += 54.0;
+	} else {
+		gmt.data.x += 31.0;
+	}
+	gmt.prev_x = gmt.data.x;
+	ctx.events.push(GameEventKind::TrooperDropped, gmt.gmo_type, gmt.data.x, gmt.data.y);
+	let (sx, sy) = gmt.sprite_pos();
+	ctx.commands.spawn(
+		GmoNew {
+			sto: ctx.sto_factory.spawn_trooper(sx, sy),
+			gmo: gmt
+		}
+	);
+}
+
+// сменить тип и спрайт объекта, сохранив центр и нижний край хитбокса
+fn morph(
+	ctx: &mut Context, gmo: &mut GameObject,
+	gmo_type: GmoType, sprite: &'static Sprite
+) {
+	let w = sprite.hitbox.w;
+	let h = sprite.hitbox.h as i32;
+	gmo.data.x += (gmo.data.w as i32 - w as i32) as f32 / 2.0;
+	gmo.data.y += (gmo.data.h - h) as f32;
+	gmo.data.w = w;
+	gmo.data.h = h;
+	gmo.prev_x = gmo.data.x;
+	gmo.prev_y = gmo.data.y;
+	gmo.gmo_type = gmo_type;
+	gmo.collide_mask = ctx.collide_matrix.get(gmo_type);
+	gmo.hit_mask = ctx.hit_masks.find(sprite.rect, false);
+	gmo.hit_dx = sprite.hitbox.x;
+	gmo.hit_dy = sprite.hitbox.y;
+	ctx.stage.set_drawable(gmo.sto_index, &sprite.drawable);
+}
+
+fn set_motion(ctx: &mut Context, gmo: &GameObject, dx: f32, dy: f32) {
+	let bhv_data = ctx.storage.get_mut::<BhvDataMove>(gmo.bhvd.get::<BhvDataMove>());
+	bhv_data.dx = dx;
+	bhv_data.dy = dy;
+}
+
+fn set_timer(ctx: &mut Context, gmo: &GameObject, delay: TimerDelay) {
+	let bhv_data = ctx.storage.get_mut::<BhvDataTimer>(gmo.bhvd.get::<BhvDataTimer>());
+	ctx.scheduler.cancel(bhv_data.timer);
+	bhv_data.timer = ctx.scheduler.once(&mut ctx.rand, delay);
+}
+
+fn enter_chute(ctx: &mut Context, gmo: &mut GameObject) {
+	let sprites = ctx.gmo_factory.sprites;
+	morph(ctx, gmo, GmoType::CHUTE, sprites.chute);
+	set_motion(ctx, gmo, 0.0, 3.0);
+}
+
+fn enter_falling(ctx: &mut Context, gmo: &mut GameObject) {
+	let sprites = ctx.gmo_factory.sprites;
+	morph(ctx, gmo, GmoType::FALLING, sprites.falling);
+	ctx.stage.set_drawable(gmo.sto_index, sprites.flail);
+	ctx.stage.flash(gmo.sto_index, HIT_FLASH_FRAMES);
+	set_motion(ctx, gmo, 0.0, 6.0);
+}
+
+fn enter_landed(ctx: &mut Context, gmo: &mut GameObject) {
+	let sprites = ctx.gmo_factory.sprites;
+	morph(ctx, gmo, GmoType::STANDING, sprites.trooper);
+	set_motion(ctx, gmo, 0.0, 0.0);
+	set_timer(ctx, gmo, TimerDelay::frames(20));
+	ctx.events.push(GameEventKind::TrooperLanded, GmoType::STANDING, gmo.data.x, gmo.data.y);
+}
+
+// идти к пушке и остановиться на 1/20 ширины сцены от центра
+fn enter_walking(ctx: &mut Context, gmo: &mut GameObject) {
+	let center = ctx.world.rect.x as f32 + ctx.world.rect.w as f32 / 2.0;
+	let stop = ctx.stage.w as f32 / 20.0;
+	let mid = gmo.data.x + gmo.data.w as f32 / 2.0;
+	let dx = if mid < center { 1.0 } else { -1.0 };
+	set_motion(ctx, gmo, dx, 0.0);
+	set_timer(ctx, gmo, TimerDelay::frames(((center - mid).abs() - stop).max(1.0) as u32));
+	let sprites = ctx.gmo_factory.sprites;
+	ctx.stage.set_drawable(gmo.sto_index, sprites.walk);
+}
+
+// дошёл до пушки: удар по ней, через несколько кадров объект удаляется
+fn enter_idle(ctx: &mut Context, gmo: &mut GameObject) {
+	let sprites = ctx.gmo_factory.sprites;
+	set_motion(ctx, gmo, 0.0, 0.0);
+	ctx.stage.set_drawable(gmo.sto_index, &sprites.trooper.drawable);
+	set_timer(ctx, gmo, TimerDelay::frames(GUN_HIT_FRAMES));
+	ctx.events.push(GameEventKind::GunHit, gmo.gmo_type, gmo.data.x, gmo.data.y);
+}
+
+// взрыв на месте объекта data: своя картинка в реестре сцены с радиусом
+// по размеру объекта; по окончании удаляется вместе с объектом сцены
+pub fn spawn_explosion(ctx: &mut Context, data: &GmoData) {
+	let drawable = DrawableExplosion {
+		color: Color::RGB(255, 160, 32),
+		r: data.w.max(data.h as u32) as f32 / 2.0,
+		duration: EXPLOSION_FRAMES,
+		on_done: Some(remove_explosion)
+	};
+	let handle = ctx.stage.drawables.add(Box::new(drawable));
+	let x = (data.x + data.w as f32 / 2.0).round() as i32;
+	let y = (data.y + data.h as f32 / 2.0).round() as i32;
+	let sto = StageObject::new(x, y, 0, handle).layer(Layer::EFFECTS).owned();
+	if ctx.stage.add_child(sto) == ctx.stage.pantry_sto.capacity {
+		ctx.stage.drawables.remove(handle);
+	}
+}
+
+fn remove_explosion(ctx: &mut Context, sto_index: usize) {
+	ctx.stage.remove_child(sto_index);
+}
+
+// общие поля нового объекта; предыдущая позиция совпадает с текущей,
+// чтобы первый шаг не интерполировался из (0, 0)
+fn new_gmo(
+	ctx: &Context, gmo_type: GmoType, data: GmoData,
+	bhv: &'static BhvMachine, bhv_state: usize, bhvd: BhvDataSet
+) -> GameObject {
+	GameObject {
+		gmo_type: gmo_type,
+		sto_index: 0,
+		data: data,
+		collide_mask: ctx.collide_matrix.get(gmo_type),
+		prev_x: data.x,
+		prev_y: data.y,
+		hit_mask: None,
+		hit_dx: 0,
+		hit_dy: 0,
+		fade_frames: 0,
+		bhv: bhv,
+		bhv_state: bhv_state,
+		bhvd: bhvd
+	}
+}
+
+// размер по хитбоксу спрайта и его маска; при flip_x хитбокс отражается
+fn with_sprite(
+	ctx: &Context, mut gmo: GameObject, sprite: &'static Sprite, flip_x: bool
+) -> GameObject {
+	gmo.data.w = sprite.hitbox.w;
+	gmo.data.h = sprite.hitbox.h as i32;
+	gmo.hit_mask = ctx.hit_masks.find(sprite.rect, flip_x);
+	gmo.hit_dx = if flip_x {
+		(sprite.rect.w - sprite.hitbox.w) as i32 - sprite.hitbox.x
+	} else {
+		sprite.hitbox.x
+	};
+	gmo.hit_dy = sprite.hitbox.y;
+	gmo
 }
 
+// spawn_* возвращают None, если в хранилище данных поведения нет места;
+// уже занятые записи при этом освобождаются
 impl GmoFactory
 {
 	pub fn spawn_shot(
-		&self, ctx: &mut Context, x: i32, y: i32, bhv_data: BhvDataMove
-	) -> GameObject {
-		GameObject {
-			gmo_type: GmoType::SHOT,
-			sto_index: 0,
-			data: GmoData { x: x, y: y, w: 3, h: 3 },
-			collide_mask: CollideMask {
-				src: CollideGroup::SHOT, dst: CollideGroup::AERIAL
-			},
-			bhv: &BehaviourMove {},
-			bhvd_index: ctx.storage.pantry_bhvd_move.alloc(bhv_data)
-		}
+		&self, ctx: &mut Context, x: f32, y: f32, bhv_data: BhvDataMove
+	) -> Option<GameObject> {
+		let bhvd = BhvDataSet::new(&[ctx.storage.alloc(bhv_data)?]);
+		Some(new_gmo(ctx, GmoType::SHOT, GmoData { x: x, y: y, w: 3, h: 3 }, &BHV_MOVE, 0, bhvd))
 	}
 
 	pub fn spawn_carrier(
-		&self, ctx: &mut Context, x: i32, y: i32, bhv_data: BhvDataTimedMotion
-	) -> GameObject {
-		GameObject {
-			gmo_type: GmoType::CARRIER,
-			sto_index: 0,
-			data: GmoData { x: x, y: y, w: 101, h: 50 },
-			collide_mask: CollideMask {
-				src: CollideGroup::AERIAL, dst: CollideGroup::NONE
-			},
-			bhv: &BehaviourCarrier {},
-			bhvd_index: ctx.storage.pantry_bhvd_tm.alloc(bhv_data)
-		}
+		&self, ctx: &mut Context, x: f32, y: f32, speed: f32, delay: TimerDelay
+	) -> Option<GameObject> {
+		let bhvd = ctx.storage.alloc_pair(
+			BhvDataMove { dx: speed, dy: 0.0 },
+			BhvDataSpawner::new(delay)
+		)?;
+		// летящий влево отражён, хитбокс отражается вместе с ним
+		let gmo = new_gmo(ctx, GmoType::CARRIER, GmoData::at(x, y), &BHV_CARRIER, 0, bhvd);
+		let mut gmo = with_sprite(ctx, gmo, self.sprites.carrier, speed < 0.0);
+		gmo.fade_frames = CARRIER_FADE_FRAMES;
+		Some(gmo)
 	}
 
 	pub fn spawn_gun(
-		&self, ctx: &mut Context, x: i32, y: i32, bhv_data: BhvDataGun
-	) -> GameObject {
-		GameObject {
-			gmo_type: GmoType::GUN,
-			sto_index: 0,
-			data: GmoData { x: x, y: y, w: 32, h: 37 },
-			collide_mask: CollideMask {
-				src: CollideGroup::GUN, dst: CollideGroup::NONE
-			},
-			bhv: &BehaviourGun {},
-			bhvd_index: ctx.storage.pantry_bhvd_gun.alloc(bhv_data)
-		}
+		&self, ctx: &mut Context, x: f32, y: f32, bhv_data: BhvDataGun, delay: TimerDelay
+	) -> Option<GameObject> {
+		let bhvd = ctx.storage.alloc_pair(bhv_data, BhvDataSpawner::new(delay))?;
+		let data = GmoData { x: x, y: y, w: GUN_W, h: GUN_H as i32 };
+		Some(new_gmo(ctx, GmoType::GUN, data, &BHV_GUN, 0, bhvd))
 	}
 
-	pub fn spawn_trooper(&self, ctx: &mut Context, x: i32, y: i32) -> GameObject
+	pub fn spawn_trooper(&self, ctx: &mut Context, x: f32, y: f32) -> Option<GameObject>
 	{
-		let delay = ctx.rand.randint(10, 40);
-		let bhvd_index = ctx.storage.pantry_bhvd_tm.alloc(
-			BhvDataTimedMotion { speed: 6, delay: delay }
-		);
-		GameObject {
-			gmo_type: GmoType::FALLING,
-			sto_index: 0,
-			data: GmoData { x: x, y: y, w: 17, h: 26 },
-			collide_mask: CollideMask {
-				src: CollideGroup::AERIAL, dst: CollideGroup::NONE
-			},
-			bhv: &BehaviourTrooper {},
-			bhvd_index: bhvd_index
-		}
+		let bhvd = ctx.storage.alloc_pair(
+			BhvDataMove { dx: 0.0, dy: 6.0 },
+			BhvDataTimer { timer: TimerHandle::NONE }
+		)?;
+		// таймер заводится, только когда место под данные уже нашлось
+		let timer = ctx.scheduler.once(&mut ctx.rand, TimerDelay::FRAMES(10, 40));
+		ctx.storage.get_mut::<BhvDataTimer>(bhvd.get::<BhvDataTimer>()).timer = timer;
+		let gmo = new_gmo(ctx, GmoType::FALLING, GmoData::at(x, y), &BHV_TROOPER, TROOPER_DROP, bhvd);
+		Some(with_sprite(ctx, gmo, self.sprites.trooper, false))
 	}
 
-	pub fn spawn_falling(&self, ctx: &mut Context, x: i32, y: i32) -> GameObject
+	pub fn spawn_falling(&self, ctx: &mut Context, x: f32, y: f32) -> Option<GameObject>
 	{
-		GameObject {
-			gmo_type: GmoType::FALLING,
-			sto_index: 0,
-			data: GmoData { x: x, y: y, w: 16, h: 25 },
-			collide_mask: CollideMask {
-				src: CollideGroup::AERIAL, dst: CollideGroup::NONE
-			},
-			bhv: &BehaviourMove {},
-			bhvd_index: ctx.storage.pantry_bhvd_move.alloc(
-				BhvDataMove { dx: 0, dy: 6 }
-			)
-		}
+		let bhvd = BhvDataSet::new(&[ctx.storage.alloc(BhvDataMove { dx: 0.0, dy: 6.0 })?]);
+		let gmo = new_gmo(ctx, GmoType::FALLING, GmoData::at(x, y), &BHV_MOVE, 0, bhvd);
+		Some(with_sprite(ctx, gmo, self.sprites.falling, false))
 	}
 
-	pub fn spawn_chute(&self, ctx: &mut Context, x: i32, y: i32) -> GameObject
+	// парашютист, уже раскрывший парашют
+	pub fn spawn_chute(&self, ctx: &mut Context, x: f32, y: f32) -> Option<GameObject>
 	{
-		GameObject {
-			gmo_type: GmoType::CHUTE,
-			sto_index: 0,
-			data: GmoData { x: x, y: y, w: 41, h: 51 },
-			collide_mask: CollideMask {
-				src: CollideGroup::AERIAL, dst: CollideGroup::NONE
-			},
-			bhv: &BehaviourMove {},
-			bhvd_index: ctx.storage.pantry_bhvd_move.alloc(
-				BhvDataMove { dx: 0, dy: 3 }
-			)
-		}
+		let bhvd = ctx.storage.alloc_pair(
+			BhvDataMove { dx: 0.0, dy: 3.0 },
+			BhvDataTimer { timer: TimerHandle::NONE }
+		)?;
+		let gmo = new_gmo(ctx, GmoType::CHUTE, GmoData::at(x, y), &BHV_TROOPER, TROOPER_CHUTE, bhvd);
+		Some(with_sprite(ctx, gmo, self.sprites.chute, false))
 	}
 
-	pub fn spawn_splosh(&self, ctx: &mut Context, x: i32, y: i32, speed: i32) -> GameObject
+	pub fn spawn_splosh(&self, ctx: &mut Context, x: f32, y: f32, speed: f32) -> Option<GameObject>
 	{
-		GameObject {
-			gmo_type: GmoType::SPLOSH,
-			sto_index: 0,
-			data: GmoData { x: x, y: y, w: 0, h: 0 },
-			collide_mask: CollideMask {
-				src: CollideGroup::NONE, dst: CollideGroup::NONE
+		let bhvd = BhvDataSet::new(&[ctx.storage.alloc(BhvDataMove { dx: 0.0, dy: speed })?]);
+		let mut gmo = new_gmo(
+			ctx, GmoType::SPLOSH, GmoData { x: x, y: y, w: 0, h: 0 }, &BHV_FALL, 0, bhvd
+		);
+		gmo.fade_frames = SPLOSH_FADE_FRAMES;
+		Some(gmo)
+	}
+
+	// объект типа gmo_type с параметрами по умолчанию
+	pub fn spawn_type(
+		&self, ctx: &mut Context, gmo_type: GmoType, x: f32, y: f32
+	) -> Option<GmoNew> {
+		let sto_factory = ctx.sto_factory;
+		let gmo = match gmo_type {
+			GmoType::FALLING => self.spawn_falling(ctx, x, y)?,
+			GmoType::CHUTE => self.spawn_chute(ctx, x, y)?,
+			_ => return None
+		};
+		let (sx, sy) = gmo.sprite_pos();
+		let sto = match gmo_type {
+			GmoType::FALLING => sto_factory.spawn_falling(sx, sy),
+			_ => sto_factory.spawn_chute(sx, sy)
+		};
+		Some(GmoNew { gmo: gmo, sto: sto })
+	}
+
+	// один обломок, разлетающийся из области data
+	pub fn spawn_debris(
+		&self, ctx: &mut Context, gmo_type: GmoType, data: &GmoData
+	) -> Option<GmoNew> {
+		match gmo_type {
+			GmoType::SPLOSH => {
+				let pos = ctx.rand.randint(0, data.w * 2) as f32 - (data.w / 2) as f32;
+				let speed = -(ctx.rand.randint(3, 12) as f32);
+				let gmo = self.spawn_splosh(ctx, data.x + pos, data.y, speed)?;
+				let sto = ctx.sto_factory.spawn_splosh(data.x as i32, data.y as i32);
+				Some(GmoNew { sto: sto, gmo: gmo })
 			},
-			bhv: &BehaviourGravityMove {},
-			bhvd_index: ctx.storage.pantry_bhvd_tm.alloc(
-				BhvDataTimedMotion { speed: speed, delay: 0 }
-			)
+			_ => None
 		}
 	}
 }
 
 impl StoFactory
 {
+	// составные картинки живут в реестре сцены до конца программы
+	pub fn create(sprites: GameSprites, drawables: &mut DrawableRegistry) -> Self {
+		let carrier = DrawableGroup { parts: vec![&sprites.carrier.drawable, &DR_ROTOR] };
+		StoFactory { sprites: sprites, carrier: drawables.add(Box::new(carrier)) }
+	}
+
 	pub fn spawn_logo(&self, x: i32, y: i32) -> StageObject
 	{
-		StageObject { x: x, y: y, angle: 0, drawable: &DR_LOGO }
+		StageObject::new(x, y, 0, &self.sprites.logo.drawable).layer(Layer::BACKGROUND)
 	}
 
 	pub fn spawn_shot(&self, x: i32, y: i32) -> StageObject
 	{
-		StageObject { x: x, y: y, angle: 0, drawable: &DR_SHOT }
+		StageObject::new(x, y, 0, &DR_SHOT).layer(Layer::PROJECTILES)
 	}
 
 	pub fn spawn_splosh(&self, x: i32, y: i32) -> StageObject
 	{
-		StageObject { x: x, y: y, angle: 0, drawable: &DR_SPLOSH }
+		StageObject::new(x, y, 0, &DR_SPLOSH).layer(Layer::EFFECTS)
 	}
 
-	pub fn spawn_carrier(&self, x: i32, y: i32, speed: i32) -> StageObject
+	pub fn spawn_carrier(&self, x: i32, y: i32, speed: f32) -> StageObject
 	{
-		StageObject {
-			x: x, y: y, angle: 0,
-			drawable: if speed < 0 { &DR_CARRIER_LEFT } else { &DR_CARRIER_RIGHT }
-		}
+		StageObject::new(x, y, 0, self.carrier)
+			.flip(speed < 0.0, false).layer(Layer::ENEMIES)
 	}
 
 	pub fn spawn_gun(&self, x: i32, y:i32) -> StageObject
 	{
-		StageObject { x: x, y: y, angle: 0, drawable: &DR_GUN }
+		StageObject::new(x, y, 0, &DR_GUN).layer(Layer::GROUND)
 	}
 
 	pub fn spawn_trooper(&self, x: i32, y: i32) -> StageObject
 	{
-		StageObject { x: x, y: y,  angle: 0, drawable: &DR_TROOPER }
+		StageObject::new(x, y, 0, &self.sprites.trooper.drawable).layer(Layer::ENEMIES)
 	}
 
 	pub fn spawn_falling(&self, x: i32, y: i32) -> StageObject
 	{
-		StageObject { x: x, y: y,  angle: 0, drawable: &DR_FALLING }
+		StageObject::new(x, y, 0, self.sprites.flail).layer(Layer::ENEMIES)
 	}
 
 	pub fn spawn_chute(&self, x: i32, y: i32) -> StageObject
 	{
-		StageObject { x: x, y: y, angle: 0, drawable: &DR_CHUTE }
+		StageObject::new(x, y, 0, &self.sprites.chute.drawable).layer(Layer::ENEMIES)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::scheduler;
+
+	// приземлившийся парашютист доходит до пушки, бьёт её и исчезает
+	#[test]
+	fn landed_trooper_hits_gun_and_ends() {
+		let mut ctx = Context::for_tests(8);
+		let gmo_factory = ctx.gmo_factory;
+		let mut gmo = gmo_factory.spawn_chute(&mut ctx, 10.0, 0.0).unwrap();
+		gmo.sto_index = ctx.stage.add_child(ctx.sto_factory.spawn_chute(10, 0));
+		gmo.data.y = ctx.stage.h as f32;
+
+		let mut status = BhvStatus::OK;
+		for _ in 0..2000 {
+			scheduler::tick(&mut ctx);
+			status = gmo.update(&mut ctx, 0);
+			if status == BhvStatus::END {
+				break;
+			}
+		}
+		assert!(status == BhvStatus::END);
+		let hits = ctx.events.iter().filter(|e| e.kind == GameEventKind::GunHit).count();
+		assert_eq!(hits, 1);
+		gmo.free(&mut ctx);
+		assert_eq!(ctx.scheduler.active_cnt(), 0);
+		assert!(ctx.storage.leak_report().is_empty());
 	}
 }
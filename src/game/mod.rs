@@ -1,11 +1,13 @@
-use crate::renderer::Drawable;
-use crate::behaviour::Behaviour;
+use crate::behaviour::{ BhvMachine, BhvSignal, BhvStatus };
 use crate::Context;
 use crate::pantry::*;
 use crate::renderer::*;
 use crate::collider::*;
+use crate::storage::BhvDataSet;
+use crate::camera::Camera;
+use sdl2::pixels::Color;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub struct PlainRect {
 	pub x: i32,
 	pub y: i32,
@@ -13,34 +15,177 @@ pub struct PlainRect {
 	pub h: u32
 }
 
+// слои рисуются снизу вверх в порядке объявления
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+	BACKGROUND, GROUND, ENEMIES, PROJECTILES, EFFECTS, HUD
+}
+
+pub const LAYER_CNT: usize = Layer::HUD as usize + 1;
+
+impl Layer {
+	pub const ALL: [Layer; LAYER_CNT] = [
+		Layer::BACKGROUND, Layer::GROUND, Layer::ENEMIES,
+		Layer::PROJECTILES, Layer::EFFECTS, Layer::HUD
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Layer::BACKGROUND => "background",
+			Layer::GROUND => "ground",
+			Layer::ENEMIES => "enemies",
+			Layer::PROJECTILES => "projectiles",
+			Layer::EFFECTS => "effects",
+			Layer::HUD => "hud"
+		}
+	}
+}
+
+// вызывается по окончании цикла анимации объекта sto_index
+pub type AnimCallback = fn(ctx: &mut Context, sto_index: usize);
+
+// у дочернего объекта x, y и angle заданы относительно родителя:
+// смещение поворачивается вместе с родителем, углы складываются
+#[derive(Copy, Clone)]
 pub struct StageObject {
 	pub x: i32,
 	pub y: i32,
+	pub prev_x: i32,	// позиция на предыдущем шаге симуляции,
+	pub prev_y: i32,	// для интерполяции при отрисовке
 	pub angle: i32,
-	pub drawable: &'static dyn Drawable
+	pub flip_x: bool,
+	pub flip_y: bool,
+	pub scale: f32,
+	pub tint: Color,	// умножается на цвет картинки
+	pub alpha: u8,
+	pub flash: u32,		// кадров белой вспышки
+	fade: u32,			// кадров до удаления при затухании
+	fade_total: u32,
+	pub layer: Layer,
+	pub z: i32,			// порядок внутри слоя
+	seq: u64,			// порядок добавления на сцену, назначает Stage
+	pub visible: bool,	// скрытый объект скрывает и своих детей
+	parent: Option<usize>,
+	pub anim: AnimState,
+	pub drawable: DrawableRef,
+	owns_drawable: bool	// запись реестра удаляется вместе с объектом
 }
 
+impl StageObject {
+	pub fn new(x: i32, y: i32, angle: i32, drawable: impl Into<DrawableRef>) -> Self {
+		StageObject {
+			x: x, y: y, prev_x: x, prev_y: y, angle: angle,
+			flip_x: false, flip_y: false, scale: 1.0,
+			tint: Color::RGB(255, 255, 255), alpha: 255, flash: 0, fade: 0, fade_total: 0,
+			layer: Layer::BACKGROUND, z: 0, seq: 0,
+			visible: true, parent: None, anim: AnimState::new(),
+			drawable: drawable.into(), owns_drawable: false
+		}
+	}
+
+	// картинка из реестра отдаётся объекту: сцена удалит её вместе с ним
+	pub fn owned(mut self) -> Self {
+		self.owns_drawable = true;
+		self
+	}
+
+	pub fn layer(mut self, layer: Layer) -> Self {
+		self.layer = layer;
+		self
+	}
+
+	pub fn z(mut self, z: i32) -> Self {
+		self.z = z;
+		self
+	}
+
+	pub fn flip(mut self, flip_x: bool, flip_y: bool) -> Self {
+		self.flip_x = flip_x;
+		self.flip_y = flip_y;
+		self
+	}
+
+	pub fn scale(mut self, scale: f32) -> Self {
+		self.scale = scale;
+		self
+	}
+
+	pub fn tint(mut self, tint: Color) -> Self {
+		self.tint = tint;
+		self
+	}
+
+	pub fn alpha(mut self, alpha: u8) -> Self {
+		self.alpha = alpha;
+		self
+	}
+
+	// цвет картинки с учётом оттенка, прозрачности и вспышки
+	pub fn modulate(&self, color: Color) -> Color {
+		let a = (color.a as u32 * self.alpha as u32 / 255) as u8;
+		if self.flash > 0 {
+			return Color::RGBA(255, 255, 255, a);
+		}
+		Color::RGBA(
+			(color.r as u32 * self.tint.r as u32 / 255) as u8,
+			(color.g as u32 * self.tint.g as u32 / 255) as u8,
+			(color.b as u32 * self.tint.b as u32 / 255) as u8,
+			a
+		)
+	}
+
+	pub fn parent(&self) -> Option<usize> {
+		self.parent
+	}
+}
+
+// положение объекта на экране с учётом всех родителей
 #[derive(Copy, Clone)]
-pub struct GmoData {
+pub struct StageTransform {
 	pub x: i32,
 	pub y: i32,
+	pub angle: i32,
+	pub visible: bool
+}
+
+#[derive(Copy, Clone)]
+// позиция в долях пикселя; на сцену попадает округлённой
+pub struct GmoData {
+	pub x: f32,
+	pub y: f32,
 	pub w: u32,
 	pub h: i32
 }
 
+impl GmoData {
+	// точка без размера; размер задаёт спрайт или вызывающий
+	pub fn at(x: f32, y: f32) -> Self {
+		GmoData { x: x, y: y, w: 0, h: 0 }
+	}
+}
+
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum GmoType {
 	NONE, GUN, SHOT, CARRIER, BOMBER, CHUTE,
 	FALLING, STANDING, SPLOSH, BOMB, EXPLOSION
 }
 
+pub const GMO_TYPE_CNT: usize = GmoType::EXPLOSION as usize + 1;
+
 pub struct GameObject {
 	pub gmo_type: GmoType,
 	pub data: GmoData,
 	pub collide_mask: CollideMask, 
-	pub bhv: &'static dyn Behaviour,
+	pub prev_x: f32,
+	pub prev_y: f32,
+	pub hit_mask: Option<usize>,
+	pub hit_dx: i32,	// смещение хитбокса (data) внутри спрайта
+	pub hit_dy: i32,
+	pub fade_frames: u32,	// при удалении спрайт гаснет столько кадров
+	pub bhv: &'static BhvMachine,
+	pub bhv_state: usize,
 	pub sto_index: usize,
-	pub bhvd_index: usize
+	pub bhvd: BhvDataSet
 }
 
 impl GameObject
@@ -49,18 +194,51 @@ impl GameObject
 		&mut self, ctx: &mut Context,
 		gmo: &GameObject, sto: StageObject
 	) {
-		self.bhv.free(ctx, self.bhvd_index);
+		self.release(ctx);
 		self.bhv = gmo.bhv;
-		self.bhvd_index = gmo.bhvd_index;
+		self.bhv_state = gmo.bhv_state;
+		self.bhvd = gmo.bhvd.clone();
 		self.gmo_type = gmo.gmo_type;
 		self.data = gmo.data;
+		self.prev_x = gmo.data.x;
+		self.prev_y = gmo.data.y;
 		self.collide_mask = gmo.collide_mask;
+		self.hit_mask = gmo.hit_mask;
+		self.hit_dx = gmo.hit_dx;
+		self.hit_dy = gmo.hit_dy;
+		self.fade_frames = gmo.fade_frames;
 		ctx.stage.update_child(self.sto_index, sto);
 	}
 
+	// левый верхний угол спрайта на сцене
+	pub fn sprite_pos(&self) -> (i32, i32) {
+		(self.data.x.round() as i32 - self.hit_dx, self.data.y.round() as i32 - self.hit_dy)
+	}
+
+	pub fn update(&mut self, ctx: &mut Context, index: usize) -> BhvStatus {
+		let bhv = self.bhv;
+		bhv.update(ctx, self, index)
+	}
+
+	// внешнее событие для автомата поведения (например, попадание)
+	pub fn signal(&mut self, ctx: &mut Context, signal: BhvSignal) -> BhvStatus {
+		let bhv = self.bhv;
+		bhv.signal(ctx, self, signal)
+	}
+
+	// таймеры и данные поведения; объект на сцене остаётся
+	pub fn release(&self, ctx: &mut Context) {
+		self.bhv.release(ctx, self);
+		ctx.storage.free_set(&self.bhvd);
+	}
+
 	pub fn free(&self, ctx: &mut Context) {
-		ctx.stage.remove_child(self.sto_index);
-		self.bhv.free(ctx, self.bhvd_index);
+		if self.fade_frames > 0 {
+			ctx.stage.fade_out(self.sto_index, self.fade_frames);
+		} else {
+			ctx.stage.remove_child(self.sto_index);
+		}
+		self.release(ctx);
 	}
 }
 
@@ -72,28 +250,176 @@ pub struct GmoNew {
 pub struct Stage {
 	pub w: u32,
 	pub h: u32,
-	pub pantry_sto: Pantry<StageObject>
+	pub pantry_sto: Pantry<StageObject>,
+	pub drawables: DrawableRegistry,
+	pub camera: Camera,
+	next_seq: u64,
+	layer_visible: [bool; LAYER_CNT]
 }
 
 impl Stage
 {
+	pub fn new(w: u32, h: u32, max_obj_cnt: usize) -> Self {
+		Stage {
+			w: w,
+			h: h,
+			pantry_sto: Pantry::create(max_obj_cnt),
+			drawables: DrawableRegistry::new(),
+			camera: Camera::new(),
+			next_seq: 0,
+			layer_visible: [true; LAYER_CNT]
+		}
+	}
+
 	pub fn clear(&mut self)
 	{
+		for index in self.pantry_sto.used_indices() {
+			self.release_drawable(index);
+		}
 		self.pantry_sto.clear();
+		self.camera.reset();
+		self.next_seq = 0;
 	}
 
-	pub fn add_child(&mut self, sto: StageObject) -> usize {
+	pub fn add_child(&mut self, mut sto: StageObject) -> usize {
+		sto.seq = self.next_seq;
+		self.next_seq += 1;
 		return self.pantry_sto.alloc(sto);
 	}
 
-	pub fn update_child(&mut self, index: usize, sto: StageObject) {
+	// объект, который двигается, поворачивается и удаляется вместе с parent
+	pub fn add_child_of(&mut self, parent: usize, mut sto: StageObject) -> usize {
+		sto.parent = Some(parent);
+		self.add_child(sto)
+	}
+
+	// замена сохраняет место объекта в порядке отрисовки и родителя
+	pub fn update_child(&mut self, index: usize, mut sto: StageObject) {
+		let old = self.pantry_sto.get(index);
+		sto.seq = old.seq;
+		sto.parent = old.parent;
+		self.release_drawable(index);
 		self.pantry_sto.update(index, sto);
 	}
 
+	pub fn is_layer_visible(&self, layer: Layer) -> bool {
+		self.layer_visible[layer as usize]
+	}
+
+	pub fn set_layer_visible(&mut self, layer: Layer, visible: bool) {
+		self.layer_visible[layer as usize] = visible;
+	}
+
+	pub fn toggle_layer(&mut self, layer: Layer) {
+		let visible = !self.is_layer_visible(layer);
+		self.set_layer_visible(layer, visible);
+	}
+
+	// вместе со всеми потомками
 	pub fn remove_child(&mut self, index: usize) {
+		for child in self.children(index) {
+			self.remove_child(child);
+		}
+		self.release_drawable(index);
 		self.pantry_sto.free(index);
 	}
 
+	fn release_drawable(&mut self, index: usize) {
+		let sto = self.pantry_sto.get_mut(index);
+		if sto.owns_drawable {
+			sto.owns_drawable = false;
+			if let DrawableRef::HANDLE(handle) = sto.drawable {
+				self.drawables.remove(handle);
+			}
+		}
+	}
+
+	pub fn children(&self, index: usize) -> Vec<usize> {
+		let mut children = self.pantry_sto.used_indices();
+		children.retain(|child| self.pantry_sto.get(*child).parent == Some(index));
+		children
+	}
+
+	// alpha - доля шага для интерполяции между prev и текущей позицией
+	pub fn transform(&self, index: usize, alpha: f32) -> StageTransform {
+		let sto = self.pantry_sto.get(index);
+		let x = sto.prev_x as f32 + (sto.x - sto.prev_x) as f32 * alpha;
+		let y = sto.prev_y as f32 + (sto.y - sto.prev_y) as f32 * alpha;
+		match sto.parent {
+			None => StageTransform {
+				x: x.round() as i32, y: y.round() as i32,
+				angle: sto.angle, visible: sto.visible
+			},
+			Some(parent) => {
+				let base = self.transform(parent, alpha);
+				let theta: f32 = (base.angle as f32).to_radians();
+				let cos = theta.cos();
+				let sin = theta.sin();
+				// ось y экрана направлена вниз, угол отсчитывается против часовой
+				StageTransform {
+					x: base.x + (x * cos + y * sin).round() as i32,
+					y: base.y + (y * cos - x * sin).round() as i32,
+					angle: base.angle + sto.angle,
+					visible: base.visible && sto.visible
+				}
+			}
+		}
+	}
+
+	pub fn world_transform(&self, index: usize) -> StageTransform {
+		self.transform(index, 1.0)
+	}
+
+	// новая картинка проигрывается с первого кадра
+	pub fn set_drawable(&mut self, index: usize, drawable: impl Into<DrawableRef>) {
+		self.release_drawable(index);
+		let sto = self.pantry_sto.get_mut(index);
+		sto.drawable = drawable.into();
+		sto.anim = AnimState::new();
+	}
+
+	pub fn flash(&mut self, index: usize, frames: u32) {
+		self.pantry_sto.get_mut(index).flash = frames;
+	}
+
+	// объект больше никому не принадлежит: гаснет и удаляется сам
+	pub fn fade_out(&mut self, index: usize, frames: u32) {
+		let sto = self.pantry_sto.get_mut(index);
+		sto.fade = frames.max(1);
+		sto.fade_total = sto.fade;
+	}
+
+	// следующий шаг анимации всех объектов, в том числе скрытых
+	pub fn animate(&mut self) -> Vec<(usize, AnimCallback)> {
+		let mut done = Vec::new();
+		for index in self.pantry_sto.used_indices() {
+			// потомок мог уйти вместе с погасшим родителем
+			if !self.pantry_sto.is_used(index) {
+				continue;
+			}
+			let sto = self.pantry_sto.get_mut(index);
+			if sto.flash > 0 {
+				sto.flash -= 1;
+			}
+			if sto.fade > 0 {
+				sto.fade -= 1;
+				if sto.fade == 0 {
+					self.remove_child(index);
+					continue;
+				}
+				sto.alpha = (255 * sto.fade / sto.fade_total) as u8;
+			}
+			let drawable = match self.drawables.resolve(sto.drawable) {
+				Some(drawable) => drawable,
+				None => continue
+			};
+			if let Some(callback) = drawable.animate(&mut sto.anim) {
+				done.push((index, callback));
+			}
+		}
+		done
+	}
+
 	pub fn get(&self, index: usize) -> &StageObject {
 		self.pantry_sto.get(index)
 	}
@@ -102,18 +428,92 @@ impl Stage
 		self.pantry_sto.get_mut(index)
 	}
 
-	pub fn draw(&self, renderer: &mut Renderer) {
-		if self.pantry_sto.len() > 0 {
-			let mut index = self.pantry_sto.first_index();
-	   		loop {
-				let is_last = self.pantry_sto.is_last_index(index);
-				let sto = self.pantry_sto.get(index);
-				sto.drawable.draw(sto, renderer);
-				if is_last {
-					break;
-				}
-				index = self.pantry_sto.next_index(index);
+	// запомнить позиции перед шагом симуляции
+	pub fn snapshot(&mut self) {
+		for index in self.pantry_sto.used_indices() {
+			let sto = self.pantry_sto.get_mut(index);
+			sto.prev_x = sto.x;
+			sto.prev_y = sto.y;
+		}
+	}
+
+	// alpha - доля шага между предыдущим и текущим состоянием; 1.0 - без интерполяции
+	// порядок по (слой, z, порядок добавления) не зависит от того,
+	// какие ячейки pantry освободились раньше
+	pub fn draw_order(&self) -> Vec<usize> {
+		let mut order = self.pantry_sto.used_indices();
+		order.retain(|index| self.is_layer_visible(self.pantry_sto.get(*index).layer));
+		order.sort_by_key(|index| {
+			let sto = self.pantry_sto.get(*index);
+			(sto.layer, sto.z, sto.seq)
+		});
+		order
+	}
+
+	// камера сдвигает все слои, кроме HUD; сдвиг делается здесь,
+	// поэтому drawable и рендерер о камере не знают
+	pub fn draw(&self, renderer: &mut Renderer, alpha: f32) {
+		let (cam_x, cam_y) = self.camera.offset(alpha);
+		for index in self.draw_order() {
+			let transform = self.transform(index, alpha);
+			if !transform.visible {
+				continue;
 			}
+			let mut sto = *self.pantry_sto.get(index);
+			sto.x = transform.x;
+			sto.y = transform.y;
+			sto.angle = transform.angle;
+			if sto.layer != Layer::HUD {
+				sto.x += cam_x;
+				sto.y += cam_y;
+			}
+			// удалённая из реестра картинка просто не рисуется
+			if let Some(drawable) = self.drawables.resolve(sto.drawable) {
+				drawable.draw(&sto, renderer);
+			}
+		}
+	}
+}
+
+// анимации сцены и их функции окончания; вызывается каждый шаг симуляции
+pub fn animate(ctx: &mut Context) {
+	for (index, callback) in ctx.stage.animate() {
+		callback(ctx, index);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::renderer::DrawableExplosion;
+
+	fn explosion() -> Box<DrawableExplosion> {
+		Box::new(DrawableExplosion { color: Color::RGB(0, 0, 0), r: 1.0, duration: 1, on_done: None })
+	}
+
+	#[test]
+	fn owned_drawables_go_with_objects() {
+		let mut stage = Stage::new(100, 100, 8);
+		let shared = stage.drawables.add(explosion());
+		let owned = stage.drawables.add(explosion());
+		let parent = stage.add_child(StageObject::new(0, 0, 0, shared));
+		stage.add_child_of(parent, StageObject::new(0, 0, 0, owned).owned());
+		stage.add_child(StageObject::new(0, 0, 0, shared));
+		stage.remove_child(parent);
+		assert!(stage.drawables.get(owned).is_none());
+		assert!(stage.drawables.get(shared).is_some());
+
+		let replaced = stage.drawables.add(explosion());
+		let index = stage.add_child(StageObject::new(0, 0, 0, replaced).owned());
+		stage.set_drawable(index, shared);
+		assert!(stage.drawables.get(replaced).is_none());
+
+		for _ in 0..3 {
+			let handle = stage.drawables.add(explosion());
+			stage.add_child(StageObject::new(0, 0, 0, handle).owned());
 		}
+		stage.clear();
+		assert_eq!(stage.drawables.len(), 1);
+		assert!(stage.drawables.get(shared).is_some());
 	}
 }
@@ -22,7 +22,10 @@ fn evt_transform_any_key(evt: &Event, input: &mut Input)
 {
 	input.evt = InputEvent::Empty;
 
-	match evt {
+	match *evt {
+		Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+			input.evt = InputEvent::Quit;
+		},
 		Event::KeyDown { .. } => {
 			input.evt = InputEvent::Continue;
 		},
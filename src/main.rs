@@ -5,6 +5,7 @@ extern crate sdl2;
 
 pub mod static_drawable;
 pub mod renderer;
+pub mod raster;
 pub mod factory;
 pub mod pantry;
 pub mod controller;
@@ -15,11 +16,19 @@ pub mod collider;
 pub mod xrand;
 pub mod zlib;
 pub mod png;
+pub mod event;
+pub mod command;
+pub mod storage;
+pub mod scheduler;
+pub mod clock;
+pub mod atlas;
+pub mod display;
+pub mod camera;
 
-use sdl2::event::Event;
+use sdl2::event::{ Event, WindowEvent };
+use sdl2::keyboard::Keycode;
 use sdl2::pixels::PixelFormatEnum;
-use sdl2::render::{ Texture, BlendMode, TextureAccess };
-//use sdl2::keyboard::Keycode;
+use sdl2::render::{ BlendMode, TextureAccess };
 
 use std::{ thread, time };
 
@@ -28,98 +37,149 @@ use controller::*;
 use input::*;
 use renderer::*;
 use factory::*;
-use pantry::*;
 use behaviour::*;
 use game::*;
-//use collider::*;
+use collider::{ CollideMatrix, HitMaskStore, WorldBounds };
+use atlas::Atlas;
+use event::EventQueue;
+use command::CommandBuffer;
+use storage::Storage;
+use scheduler::Scheduler;
+use clock::SimClock;
+use display::Display;
 use xrand::XRand;
 
 const MAX_OBJ_CNT: usize = 128;
-const FPS_DELAY: i32 = 33;
-const WINDOW_WIDTH: u32 = 800;
-const WINDOW_HEIGHT: u32 = 600;
-
-pub struct Storage {
-	pub pantry_bhvd_move: Pantry<BhvDataMove>,
-	pub pantry_bhvd_tm: Pantry<BhvDataTimedMotion>,
-	pub pantry_bhvd_gun: Pantry<BhvDataGun>
-}
-
-impl Storage {
-	pub fn create(capacity: usize) -> Self {
-		Storage {
-			pantry_bhvd_move: Pantry::create(capacity),
-			pantry_bhvd_tm: Pantry::create(capacity),
-			pantry_bhvd_gun: Pantry::create(1)
-		}
-	}
-}
+const SIM_STEP_MS: u32 = 33;		// шаг симуляции
+const MAX_SIM_STEPS: u32 = 5;		// больше шагов за кадр не догоняем
+const RENDER_MIN_MS: u32 = 4;		// не чаще 250 кадров, если нет vsync
+const INTERPOLATE: bool = true;
+// логическое разрешение: в нём считаются все позиции игры;
+// окно открывается в этом размере, его можно растягивать
+const LOGICAL_WIDTH: u32 = 800;
+const LOGICAL_HEIGHT: u32 = 600;
 
 pub struct Context {
 	pub stage: Stage,
 	pub storage: Storage,
 	pub gmo_factory: GmoFactory,
 	pub sto_factory: StoFactory,
-	pub vec_gmo_new: Vec<GmoNew>,
+	pub commands: CommandBuffer,
+	pub collide_matrix: CollideMatrix,
+	pub hit_masks: HitMaskStore,
+	pub world: WorldBounds,
+	pub events: EventQueue,
+	pub scheduler: Scheduler,
 	pub rand: XRand
 }
 
+impl Context {
+	// max_obj_cnt - ёмкость сцены, хранилищ и очередей
+	pub fn create(atlas: &'static Atlas, hit_masks: HitMaskStore, max_obj_cnt: usize) -> Self {
+		let sprites = GameSprites::resolve(atlas);
+		let mut stage = Stage::new(LOGICAL_WIDTH, LOGICAL_HEIGHT, max_obj_cnt);
+		let sto_factory = StoFactory::create(sprites, &mut stage.drawables);
+		let world = WorldBounds::create(PlainRect { x: 0, y: 0, w: stage.w, h: stage.h });
+		Context {
+			stage: stage,
+			gmo_factory: GmoFactory { sprites: sprites },
+			sto_factory: sto_factory,
+			storage: Storage::create(max_obj_cnt),
+			commands: CommandBuffer::create(max_obj_cnt),
+			collide_matrix: CollideMatrix::create(),
+			hit_masks: hit_masks,
+			world: world,
+			events: EventQueue::create(max_obj_cnt),
+			scheduler: Scheduler::create(SIM_STEP_MS, max_obj_cnt),
+			rand: XRand::new()
+		}
+	}
+
+	// контекст без окна и картинки атласа для модульных тестов
+	#[cfg(test)]
+	pub fn for_tests(max_obj_cnt: usize) -> Self {
+		let atlas = Box::leak(Box::new(atlas::parse(include_str!("../rds_atlas.txt"), 0)));
+		Context::create(atlas, HitMaskStore::new(), max_obj_cnt)
+	}
+}
+
+fn layer_for_key(keycode: Keycode) -> Option<Layer> {
+	match keycode {
+		Keycode::Num1 => Some(Layer::BACKGROUND),
+		Keycode::Num2 => Some(Layer::GROUND),
+		Keycode::Num3 => Some(Layer::ENEMIES),
+		Keycode::Num4 => Some(Layer::PROJECTILES),
+		Keycode::Num5 => Some(Layer::EFFECTS),
+		Keycode::Num6 => Some(Layer::HUD),
+		_ => None
+	}
+}
+
 pub fn main()
 {
 	let img = png::read_file("rds_atlas.png");
+	let atlas = atlas::read_file("rds_atlas.txt", 0);
+
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	let mut display = Display::new(LOGICAL_WIDTH, LOGICAL_HEIGHT);
+	display::parse_args(&mut display, &args);
 
 	let sdl = sdl2::init().unwrap();
 	let vss: sdl2::VideoSubsystem = sdl.video().unwrap();
 
-	let wb = sdl2::video::WindowBuilder::new(
+	let mut wb = sdl2::video::WindowBuilder::new(
 		& vss,
 		"RDS",
-		WINDOW_WIDTH,
-		WINDOW_HEIGHT
+		LOGICAL_WIDTH,
+		LOGICAL_HEIGHT
 	);
 
-	let window: sdl2::video::Window = wb.build().unwrap();
-	let cb = sdl2::render::CanvasBuilder::new(window);
+	let window: sdl2::video::Window = wb.resizable().build().unwrap();
+	let cb = sdl2::render::CanvasBuilder::new(window).present_vsync();
 	let mut canvas = cb.build().unwrap();
 
 	canvas.set_blend_mode(BlendMode::Blend);
+	display.apply(&mut canvas);
 
 	let texture_creator = canvas.texture_creator();
 
-	let mut texture = texture_creator.create_texture(
-		PixelFormatEnum::ABGR8888,
-		TextureAccess::Static,
-		img.width,
-		img.height
-	).unwrap();
-
-	let _ = texture.update(None, &img.data[0..], img.stride);
-	texture.set_blend_mode(BlendMode::Blend);
-
-	let mut renderer = Renderer {
-		canvas: &mut canvas,
-		texture_list: Vec::<Texture>::with_capacity(2)
+	let create_texture = |img: &png::Image| {
+		let mut texture = texture_creator.create_texture(
+			PixelFormatEnum::ABGR8888,
+			TextureAccess::Static,
+			img.width,
+			img.height
+		).unwrap();
+		let _ = texture.update(None, &img.data[0..], img.stride);
+		texture.set_blend_mode(BlendMode::Blend);
+		texture
 	};
+	let texture = create_texture(&img);
+	// белый силуэт атласа для вспышки при попадании
+	let flash_texture = create_texture(&img.silhouette());
+
+	let mut renderer = Renderer::new(&mut canvas, 2);
 
 	renderer.texture_list.push(texture);
+	renderer.texture_list.push(flash_texture);
+	renderer.set_flash_texture(0, 1);
 
-	let mut ctx = Context {
-		stage: Stage {
-			w: WINDOW_WIDTH,
-			h: WINDOW_HEIGHT,
-			pantry_sto: Pantry::create(MAX_OBJ_CNT),
-		},
-		gmo_factory: GmoFactory {},
-		sto_factory: StoFactory {},
-		storage: Storage::create(MAX_OBJ_CNT),
-		vec_gmo_new: Vec::with_capacity(MAX_OBJ_CNT),
-		rand: XRand::new()
-	};
+	let mut ctx = Context::create(atlas, HitMaskStore::create(&img, atlas), MAX_OBJ_CNT);
+
+	// пушка в игре одна, и её пул не должен резервировать MAX_OBJ_CNT ячеек;
+	// второго BhvDataGun не будет: spawn_gun вернёт None
+	ctx.storage.set_capacity::<BhvDataGun>(1);
+	camera::parse_args(&mut ctx.stage.camera, &args);
 
 	let mut evt_pump = sdl.event_pump().unwrap();
 	let timer = sdl.timer().unwrap();
 	let mut running = true;
-	let mut next_tick: i32 = timer.ticks() as i32 + FPS_DELAY;
+	let mut last_ticks = timer.ticks();
+	let mut stats_ticks = last_ticks;
+	let mut clock = SimClock::new(SIM_STEP_MS, MAX_SIM_STEPS);
+	clock::parse_args(&mut clock, &args);
+	// события копятся до ближайшего шага, в том числе на паузе
+	let mut pending: Vec<Event> = Vec::with_capacity(16);
 
 	let mut controller_title = ControllerTitle::new();
 	let mut controller_game = ControllerGame::new(MAX_OBJ_CNT);
@@ -130,39 +190,87 @@ pub fn main()
 	controller.begin(&mut ctx);
 
 	while running {
-		renderer.clear();
-		ctx.stage.draw(&mut renderer);
-		renderer.present();
+		let frame_start = timer.ticks();
+		let elapsed = frame_start.wrapping_sub(last_ticks);
+		last_ticks = frame_start;
 
 		for evt in evt_pump.poll_iter() {
 			match evt {
 				Event::Quit { .. } => {
 					running = false;
 				},
+				// отладочные клавиши управляют часами и не попадают в игру
+				Event::KeyDown { keycode: Some(k), .. } if clock.handle_key(k) => (),
+				Event::KeyUp { keycode: Some(k), .. } if clock::is_debug_key(k) => (),
+				// F10 - режим масштаба, F11 - полный экран
+				Event::KeyDown { keycode: Some(k), .. } if display.handle_key(renderer.canvas, k) => (),
+				Event::KeyUp { keycode: Some(k), .. } if display::is_display_key(k) => (),
+				// F12 - тряска экрана
+				Event::KeyDown { keycode: Some(k), .. } if ctx.stage.camera.handle_key(k) => (),
+				Event::KeyUp { keycode: Some(k), .. } if camera::is_camera_key(k) => (),
+				Event::Window { win_event: WindowEvent::SizeChanged(..), .. } => {
+					display.apply(renderer.canvas);
+				},
+				// в отладочной сборке 1..6 включают и выключают слои сцены
+				Event::KeyDown { keycode: Some(k), .. } if cfg!(debug_assertions)
+					&& layer_for_key(k).is_some() =>
+				{
+					ctx.stage.toggle_layer(layer_for_key(k).unwrap());
+				},
+				Event::KeyUp { keycode: Some(k), .. } if cfg!(debug_assertions)
+					&& layer_for_key(k).is_some() => (),
 				Event::KeyDown { .. } | Event::KeyUp { .. } => {
-					input.set_event(&evt);
+					pending.push(evt);
 				},
 				_ => ()
 			}
 		}
 
-		let evt = controller.run(&mut ctx, &input);
-		if evt == ControllerEvent::EndTitle {
-			controller.end(&mut ctx);
-			controller = &mut controller_game;
-			input = InputBuilder::game();
-			controller.begin(&mut ctx);
-		} else if evt == ControllerEvent::EndGame {
-			controller.end(&mut ctx);
-			running = false;
+		// симуляция идёт шагами фиксированной длины независимо от отрисовки
+		let steps = clock.advance(elapsed);
+		for _ in 0..steps {
+			if !running {
+				break;
+			}
+			for evt in pending.drain(..) {
+				input.set_event(&evt);
+			}
+
+			ctx.stage.snapshot();
+			let evt = controller.run(&mut ctx, &input);
+			ctx.stage.camera.update();
+			game::animate(&mut ctx);
+			if evt == ControllerEvent::EndTitle {
+				controller.end(&mut ctx);
+				controller = &mut controller_game;
+				input = InputBuilder::game();
+				controller.begin(&mut ctx);
+			} else if evt == ControllerEvent::EndGame {
+				controller.end(&mut ctx);
+				controller = &mut controller_title;
+				input = InputBuilder::any_key();
+				controller.begin(&mut ctx);
+			} else if evt == ControllerEvent::Quit {
+				running = false;
+			}
+		}
+
+		let alpha = if INTERPOLATE { clock.alpha() } else { 1.0 };
+		renderer.clear();
+		ctx.stage.draw(&mut renderer, alpha);
+		renderer.present();
+
+		// в отладочной сборке раз в секунду показать число вызовов отрисовки
+		if cfg!(debug_assertions) && frame_start.wrapping_sub(stats_ticks) >= 1000 {
+			stats_ticks = frame_start;
+			let title = format!("RDS - {} draw calls", renderer.last_draw_calls());
+			let _ = renderer.canvas.window_mut().set_title(&title);
 		}
 
-		let mut diff: i32 = next_tick - timer.ticks() as i32;
-		while diff > 0 {
-			thread::sleep(time::Duration::from_millis(diff as u64));
-			diff = next_tick - timer.ticks() as i32;
+		let elapsed = timer.ticks().wrapping_sub(frame_start);
+		if elapsed < RENDER_MIN_MS {
+			thread::sleep(time::Duration::from_millis((RENDER_MIN_MS - elapsed) as u64));
 		}
-		next_tick += FPS_DELAY;
 	}
 
 	controller.end(&mut ctx);
@@ -1,6 +1,7 @@
 pub struct ListEntry<T> {
 	pub prev: usize,
 	pub next: usize,
+	pub used: bool,
 	pub payload: T
 }
 
@@ -72,6 +73,27 @@ impl<T> Pantry<T>
 		return self.used_cnt;
 	}
 
+	pub fn is_used(&self, index: usize) -> bool
+	{
+		index < self.entries.len() && self.entries[index].used
+	}
+
+	pub fn used_indices(&self) -> Vec<usize>
+	{
+		let mut indices = Vec::with_capacity(self.used_cnt);
+		if self.used_cnt > 0 {
+			let mut index = self.used_first;
+			loop {
+				indices.push(index);
+				if index == self.used_last {
+					break;
+				}
+				index = self.entries[index].next;
+			}
+		}
+		indices
+	}
+
 	pub fn alloc(&mut self, p: T) -> usize
 	{
 		let mut index: usize = self.entries.len();
@@ -79,6 +101,7 @@ impl<T> Pantry<T>
 		let entry = ListEntry {
 			prev: self.used_last,
 			next: index,
+			used: true,
 			payload: p
 		};
 
@@ -122,6 +145,13 @@ impl<T> Pantry<T>
 				std::any::type_name::<T>(), index, self.entries.len(), self.used_cnt
 			);
 		}
+		if !self.entries[index].used {
+			panic!(
+				"free({}): index {} is already free",
+				std::any::type_name::<T>(), index
+			);
+		}
+		self.entries[index].used = false;
 		let prev = self.entries[index].prev;
 		let next = self.entries[index].next;
 
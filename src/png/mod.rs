@@ -23,6 +23,19 @@ pub struct Image {
 	pub data: Vec<u8>
 }
 
+impl Image {
+	// белый силуэт: цвет каждого пикселя белый, альфа прежняя
+	pub fn silhouette(&self) -> Image {
+		let mut data = self.data.clone();
+		for pixel in data.chunks_exact_mut(self.bpp) {
+			pixel[0] = 255;
+			pixel[1] = 255;
+			pixel[2] = 255;
+		}
+		Image { width: self.width, height: self.height, bpp: self.bpp, stride: self.stride, data: data }
+	}
+}
+
 struct Filter {
 	pos: usize
 }
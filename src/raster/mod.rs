@@ -0,0 +1,335 @@
+// растеризация многоугольников в горизонтальные отрезки
+//
+// пиксель (x, y) - квадрат [x, x+1) x [y, y+1), его центр (x+0.5, y+0.5).
+// правило верхнего левого края: пиксель закрашен, если центр внутри фигуры
+// или лежит на верхнем или левом ребре; соседние фигуры с общим ребром
+// не закрашивают пиксели дважды и не оставляют щелей
+
+use std::f32::consts::PI;
+
+// закрашенный отрезок [x0, x1) строки y; alpha - доля покрытия
+#[derive(Copy, Clone)]
+pub struct Span {
+	pub y: i32,
+	pub x0: i32,
+	pub x1: i32,
+	pub alpha: u8
+}
+
+// какие области внутри самопересекающегося многоугольника или
+// нескольких контуров считаются закрашенными
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum FillRule {
+	EVENODD,	// дырки при любом обходе контуров
+	NONZERO		// дырки только у контуров с обратным обходом
+}
+
+// подстрок на строку при сглаживании
+const AA_SUBSTEPS: i32 = 4;
+// уровней прозрачности сглаженных краёв; меньше уровней - меньше пакетов отрисовки
+const AA_LEVELS: u32 = 16;
+
+struct Edge {
+	x0: f32,
+	y0: f32,
+	y1: f32,
+	dxdy: f32,
+	dir: i32
+}
+
+fn collect_edges(contours: &[&[(f32, f32)]]) -> (Vec<Edge>, f32, f32) {
+	let mut edges = Vec::new();
+	let mut min_y = f32::MAX;
+	let mut max_y = f32::MIN;
+	for contour in contours.iter() {
+		let cnt = contour.len();
+		if cnt < 3 {
+			continue;
+		}
+		for i in 0..cnt {
+			let (ax, ay) = contour[i];
+			let (bx, by) = contour[(i + 1) % cnt];
+			min_y = min_y.min(ay);
+			max_y = max_y.max(ay);
+			// горизонтальное ребро не пересекает ни одну строку
+			if ay == by {
+				continue;
+			}
+			let (x0, y0, x1, y1, dir) = if ay < by {
+				(ax, ay, bx, by, 1)
+			} else {
+				(bx, by, ax, ay, -1)
+			};
+			edges.push(Edge { x0: x0, y0: y0, y1: y1, dxdy: (x1 - x0) / (y1 - y0), dir: dir });
+		}
+	}
+	(edges, min_y, max_y)
+}
+
+// пересечения строки sy с рёбрами, отсортированные по x;
+// верхний конец ребра включается, нижний нет
+fn crossings(edges: &[Edge], sy: f32, out: &mut Vec<(f32, i32)>) {
+	out.clear();
+	for edge in edges.iter() {
+		if sy >= edge.y0 && sy < edge.y1 {
+			out.push((edge.x0 + (sy - edge.y0) * edge.dxdy, edge.dir));
+		}
+	}
+	out.sort_by(|a, b| a.0.total_cmp(&b.0));
+}
+
+// закрашенные интервалы по x для одной строки
+fn intervals(crossings: &[(f32, i32)], rule: FillRule, out: &mut Vec<(f32, f32)>) {
+	out.clear();
+	let mut winding = 0;
+	for i in 0..crossings.len() {
+		let inside = match rule {
+			FillRule::EVENODD => i % 2 == 0,
+			FillRule::NONZERO => {
+				winding += crossings[i].1;
+				winding != 0
+			}
+		};
+		if inside && i + 1 < crossings.len() {
+			out.push((crossings[i].0, crossings[i + 1].0));
+		}
+	}
+}
+
+// заливка без сглаживания; отрезки добавляются в out
+pub fn fill(contours: &[&[(f32, f32)]], rule: FillRule, out: &mut Vec<Span>) {
+	let (edges, min_y, max_y) = collect_edges(contours);
+	if edges.is_empty() {
+		return;
+	}
+	let mut xs = Vec::with_capacity(8);
+	let mut ivs = Vec::with_capacity(4);
+	// строки, центры которых лежат в [min_y, max_y)
+	let y_start = (min_y - 0.5).ceil() as i32;
+	let y_end = (max_y - 0.5).ceil() as i32;
+	for y in y_start..y_end {
+		crossings(&edges, y as f32 + 0.5, &mut xs);
+		intervals(&xs, rule, &mut ivs);
+		for &(left, right) in ivs.iter() {
+			let x0 = (left - 0.5).ceil() as i32;
+			let x1 = (right - 0.5).ceil() as i32;
+			if x1 > x0 {
+				out.push(Span { y: y, x0: x0, x1: x1, alpha: 255 });
+			}
+		}
+	}
+}
+
+// заливка со сглаженными краями: покрытие пикселя считается по
+// AA_SUBSTEPS подстрокам с точной долей по горизонтали
+pub fn fill_aa(contours: &[&[(f32, f32)]], rule: FillRule, out: &mut Vec<Span>) {
+	let (edges, min_y, max_y) = collect_edges(contours);
+	if edges.is_empty() {
+		return;
+	}
+	let mut min_x = f32::MAX;
+	let mut max_x = f32::MIN;
+	for contour in contours.iter() {
+		for &(x, _) in contour.iter() {
+			min_x = min_x.min(x);
+			max_x = max_x.max(x);
+		}
+	}
+	let left = min_x.floor() as i32;
+	let width = (max_x.ceil() as i32 - left + 1) as usize;
+	let mut row = vec![0.0f32; width];
+	let mut xs = Vec::with_capacity(8);
+	let mut ivs = Vec::with_capacity(4);
+	let sub = 1.0 / AA_SUBSTEPS as f32;
+
+	for y in min_y.floor() as i32..max_y.ceil() as i32 {
+		for cell in row.iter_mut() {
+			*cell = 0.0;
+		}
+		for s in 0..AA_SUBSTEPS {
+			crossings(&edges, y as f32 + (s as f32 + 0.5) * sub, &mut xs);
+			intervals(&xs, rule, &mut ivs);
+			for &(x0, x1) in ivs.iter() {
+				add_coverage(&mut row, left, x0, x1, sub);
+			}
+		}
+		emit_row(&row, left, y, out);
+	}
+}
+
+fn add_coverage(row: &mut [f32], left: i32, x0: f32, x1: f32, weight: f32) {
+	if x1 <= x0 {
+		return;
+	}
+	let first = x0.floor() as i32;
+	let last = x1.floor() as i32;
+	if first == last {
+		row[(first - left) as usize] += (x1 - x0) * weight;
+		return;
+	}
+	row[(first - left) as usize] += (first as f32 + 1.0 - x0) * weight;
+	for x in first + 1..last {
+		row[(x - left) as usize] += weight;
+	}
+	row[(last - left) as usize] += (x1 - last as f32) * weight;
+}
+
+// соседние пиксели с одинаковым уровнем покрытия объединяются в отрезок
+fn emit_row(row: &[f32], left: i32, y: i32, out: &mut Vec<Span>) {
+	let mut x = 0;
+	while x < row.len() {
+		let level = coverage_level(row[x]);
+		let mut end = x + 1;
+		while end < row.len() && coverage_level(row[end]) == level {
+			end += 1;
+		}
+		if level > 0 {
+			out.push(Span {
+				y: y,
+				x0: left + x as i32,
+				x1: left + end as i32,
+				alpha: (level * 255 / AA_LEVELS) as u8
+			});
+		}
+		x = end;
+	}
+}
+
+fn coverage_level(coverage: f32) -> u32 {
+	(coverage.min(1.0) * AA_LEVELS as f32).round() as u32
+}
+
+// отрезок толщиной width как четырёхугольник
+pub fn line(x0: f32, y0: f32, x1: f32, y1: f32, width: f32) -> [(f32, f32); 4] {
+	let dx = x1 - x0;
+	let dy = y1 - y0;
+	let len = (dx * dx + dy * dy).sqrt();
+	let half = width.max(1.0) / 2.0;
+	// нормаль к отрезку; у точки нулевой длины - квадрат
+	let (nx, ny) = if len > 0.0 {
+		(-dy / len * half, dx / len * half)
+	} else {
+		(0.0, half)
+	};
+	let (ex, ey) = if len > 0.0 { (0.0, 0.0) } else { (half, 0.0) };
+	[
+		(x0 + nx - ex, y0 + ny - ey),
+		(x1 + nx + ex, y1 + ny + ey),
+		(x1 - nx + ex, y1 - ny + ey),
+		(x0 - nx - ex, y0 - ny - ey)
+	]
+}
+
+// окружность как многоугольник; число сторон растёт с радиусом
+pub fn circle(cx: f32, cy: f32, r: f32) -> Vec<(f32, f32)> {
+	let cnt = ((2.0 * PI * r / 3.0).ceil() as usize).max(12);
+	let mut points = Vec::with_capacity(cnt);
+	for i in 0..cnt {
+		let theta = 2.0 * PI * i as f32 / cnt as f32;
+		points.push((cx + r * theta.cos(), cy + r * theta.sin()));
+	}
+	points
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn pixels(spans: &[Span]) -> Vec<(i32, i32)> {
+		let mut pixels = Vec::new();
+		for span in spans.iter() {
+			for x in span.x0..span.x1 {
+				pixels.push((x, span.y));
+			}
+		}
+		pixels
+	}
+
+	#[test]
+	fn adjacent_quads_share_edge() {
+		// два четырёхугольника с общим наклонным ребром (4, 0) - (6, 8)
+		let left = [(0.0, 0.0), (4.0, 0.0), (6.0, 8.0), (0.0, 8.0)];
+		let right = [(4.0, 0.0), (10.0, 0.0), (10.0, 8.0), (6.0, 8.0)];
+		let mut a = Vec::new();
+		let mut b = Vec::new();
+		fill(&[&left], FillRule::NONZERO, &mut a);
+		fill(&[&right], FillRule::NONZERO, &mut b);
+		let a = pixels(&a);
+		let b = pixels(&b);
+		assert!(a.iter().all(|p| !b.contains(p)));
+
+		let mut both = a.clone();
+		both.extend(b.iter());
+		both.sort();
+		let mut whole = Vec::new();
+		for x in 0..10 {
+			for y in 0..8 {
+				whole.push((x, y));
+			}
+		}
+		assert_eq!(both, whole);
+	}
+
+	#[test]
+	fn aa_coverage() {
+		// квадрат 4x4, сдвинутый на полпикселя: по краям покрыта половина пикселя
+		let square = [(0.5, 0.5), (4.5, 0.5), (4.5, 4.5), (0.5, 4.5)];
+		let mut spans = Vec::new();
+		fill_aa(&[&square], FillRule::NONZERO, &mut spans);
+		let alpha = |x: i32, y: i32| {
+			spans.iter().find(|s| s.y == y && s.x0 <= x && x < s.x1).map_or(0, |s| s.alpha)
+		};
+		assert_eq!(alpha(2, 2), 255);
+		assert_eq!(alpha(0, 2), 127);
+		assert_eq!(alpha(2, 0), 127);
+		assert_eq!(alpha(0, 0), 63);
+		assert_eq!(alpha(5, 2), 0);
+	}
+
+	#[test]
+	fn line_covers_its_width() {
+		let quad = line(1.0, 5.0, 9.0, 5.0, 2.0);
+		let mut spans = Vec::new();
+		fill(&[&quad], FillRule::NONZERO, &mut spans);
+		let mut expected = Vec::new();
+		for x in 1..9 {
+			for y in 4..6 {
+				expected.push((x, y));
+			}
+		}
+		let mut got = pixels(&spans);
+		got.sort();
+		assert_eq!(got, expected);
+
+		// точка превращается в квадрат со стороной width
+		let dot = line(3.0, 3.0, 3.0, 3.0, 2.0);
+		let mut spans = Vec::new();
+		fill(&[&dot], FillRule::NONZERO, &mut spans);
+		let mut got = pixels(&spans);
+		got.sort();
+		assert_eq!(got, vec![(2, 2), (2, 3), (3, 2), (3, 3)]);
+	}
+
+	#[test]
+	fn circle_area() {
+		let points = circle(20.0, 20.0, 10.0);
+		let mut spans = Vec::new();
+		fill(&[&points], FillRule::NONZERO, &mut spans);
+		let pixels = pixels(&spans);
+		let area = std::f32::consts::PI * 100.0;
+		assert!((pixels.len() as f32 - area).abs() < area * 0.03, "{} pixels", pixels.len());
+		assert!(pixels.contains(&(20, 10)) && pixels.contains(&(10, 20)));
+		assert!(!pixels.contains(&(11, 11)));
+	}
+
+	#[test]
+	fn evenodd_ring_has_hole() {
+		let outer = circle(10.0, 10.0, 8.0);
+		let inner = circle(10.0, 10.0, 4.0);
+		let mut spans = Vec::new();
+		fill(&[&outer, &inner], FillRule::EVENODD, &mut spans);
+		let pixels = pixels(&spans);
+		assert!(!pixels.contains(&(9, 9)));
+		assert!(pixels.contains(&(3, 9)));
+	}
+}
@@ -1,112 +1,491 @@
 extern crate sdl2;
 
-use sdl2::rect::Rect;
+use sdl2::rect::{ Rect, Point };
 use sdl2::pixels::Color;
 use sdl2::render::{ WindowCanvas, Texture };
-use crate::game::{ PlainRect, StageObject };
+use crate::game::{ PlainRect, StageObject, AnimCallback };
+use crate::raster::{ self, Span, FillRule };
 
 pub struct Renderer<'a> {
 	pub canvas: &'a mut WindowCanvas,
-	pub texture_list: Vec<Texture<'a>>
+	pub texture_list: Vec<Texture<'a>>,
+	flash_textures: Vec<Option<usize>>,	// белый силуэт каждой текстуры
+	commands: Vec<RenderCommand>,
+	spans: Vec<Span>,
+	draw_calls: u32,
+	last_draw_calls: u32
+}
+
+// ключ пакета: подряд идущие команды с одинаковым ключом
+// выполняются без смены состояния SDL
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum BatchKey {
+	Fill(u32),						// цвет RGBA
+	Copy(usize, u32)				// текстура, оттенок RGBA
+}
+
+#[derive(Copy, Clone)]
+enum RenderOp {
+	Fill(Rect),
+	Copy(Rect, Rect),
+	// поворот по часовой вокруг center, отражение по x и y
+	CopyEx(Rect, Rect, f64, Point, bool, bool)
+}
+
+#[derive(Copy, Clone)]
+struct RenderCommand {
+	key: BatchKey,
+	op: RenderOp
 }
 
 impl<'a> Renderer<'a> {
+	pub fn new(canvas: &'a mut WindowCanvas, texture_cnt: usize) -> Self {
+		Renderer {
+			canvas: canvas,
+			texture_list: Vec::with_capacity(texture_cnt),
+			flash_textures: Vec::with_capacity(texture_cnt),
+			commands: Vec::with_capacity(1024),
+			spans: Vec::with_capacity(256),
+			draw_calls: 0,
+			last_draw_calls: 0
+		}
+	}
+
+	// flash_handle - текстура с белым силуэтом tex_handle для вспышки
+	pub fn set_flash_texture(&mut self, tex_handle: usize, flash_handle: usize) {
+		if self.flash_textures.len() <= tex_handle {
+			self.flash_textures.resize(tex_handle + 1, None);
+		}
+		self.flash_textures[tex_handle] = Some(flash_handle);
+	}
+
 	pub fn clear(&mut self) {
+		self.commands.clear();
+		self.draw_calls = 0;
 		self.canvas.set_draw_color(Color::RGB(0, 0, 0));
 		let _ = self.canvas.fill_rect(None);
+		self.draw_calls += 1;
 	}
 
 	pub fn present(&mut self) {
+		self.flush();
 		self.canvas.present();
+		self.last_draw_calls = self.draw_calls;
+	}
+
+	// вызовов отрисовки SDL за последний показанный кадр
+	pub fn last_draw_calls(&self) -> u32 {
+		self.last_draw_calls
 	}
 
+	// x, y - левый верхний угол неповёрнутого спрайта в масштабе 1
 	pub fn draw_bitmap(
-		&mut self, x:i32, y:i32, tex_handle: usize, rect: PlainRect
+		&mut self, x:i32, y:i32, tex_handle: usize, rect: PlainRect,
+		transform: &BitmapTransform
 	) {
-		let _ = self.canvas.copy(
-			&self.texture_list[tex_handle],
-			Some(Rect::new(rect.x, rect.y, rect.w, rect.h)),
-			Some(Rect::new(x, y, rect.w, rect.h))
-		);
+		let op = Self::copy_op(x, y, rect, transform);
+		// вспышка - белый силуэт вместо спрайта, как у прямоугольников
+		if transform.flash {
+			if let Some(Some(flash_handle)) = self.flash_textures.get(tex_handle) {
+				let white = pack_color(Color::RGBA(255, 255, 255, transform.tint.a));
+				self.push(BatchKey::Copy(*flash_handle, white), op);
+				return;
+			}
+		}
+		self.push(BatchKey::Copy(tex_handle, pack_color(transform.tint)), op);
+	}
+
+	fn copy_op(x:i32, y:i32, rect: PlainRect, transform: &BitmapTransform) -> RenderOp {
+		let src = Rect::new(rect.x, rect.y, rect.w, rect.h);
+		if transform.is_identity() {
+			return RenderOp::Copy(src, Rect::new(x, y, rect.w, rect.h));
+		}
+
+		// при отражении опорная точка отражается вместе со спрайтом
+		let mut px = transform.pivot.0;
+		let mut py = transform.pivot.1;
+		if transform.flip_x {
+			px = rect.w as i32 - px;
+		}
+		if transform.flip_y {
+			py = rect.h as i32 - py;
+		}
+		// масштаб относительно опорной точки
+		let scale = transform.scale;
+		let w = (rect.w as f32 * scale).round() as u32;
+		let h = (rect.h as f32 * scale).round() as u32;
+		let cx = (px as f32 * scale).round() as i32;
+		let cy = (py as f32 * scale).round() as i32;
+		RenderOp::CopyEx(
+			src,
+			Rect::new(x + px - cx, y + py - cy, w.max(1), h.max(1)),
+			// SDL поворачивает по часовой стрелке
+			-transform.angle as f64,
+			Point::new(cx, cy),
+			transform.flip_x, transform.flip_y
+		)
 	}
 
 	pub fn draw_rect(&mut self, x:i32, y:i32, w:u32, h:u32, color:Color) {
-		self.canvas.set_draw_color(color);
-		let _ = self.canvas.fill_rect(Rect::new(x, y, w, h));
+		self.push(BatchKey::Fill(pack_color(color)), RenderOp::Fill(Rect::new(x, y, w, h)));
 	}
 
-	pub fn rasterize_poly(
-		&mut self,
-		mut x0:i32, mut y0:i32, mut x1:i32, mut y1:i32,
-		mut x2:i32, mut y2:i32, color:Color
+	fn push(&mut self, key: BatchKey, op: RenderOp) {
+		self.commands.push(RenderCommand { key: key, op: op });
+	}
+
+	// выполнить накопленные команды в порядке отрисовки сцены; в пакет
+	// объединяются только идущие подряд команды, иначе объекты одного
+	// цвета или текстуры перекрывали бы друг друга не в том порядке
+	pub fn flush(&mut self) {
+		let mut commands = std::mem::take(&mut self.commands);
+
+		let mut rects: Vec<Rect> = Vec::with_capacity(64);
+		let mut pos = 0;
+		while pos < commands.len() {
+			let key = commands[pos].key;
+			let mut end = pos;
+			while end < commands.len() && commands[end].key == key {
+				end += 1;
+			}
+			match key {
+				BatchKey::Fill(color) => {
+					rects.clear();
+					for cmd in commands[pos..end].iter() {
+						if let RenderOp::Fill(rect) = cmd.op {
+							rects.push(rect);
+						}
+					}
+					self.canvas.set_draw_color(unpack_color(color));
+					let _ = self.canvas.fill_rects(&rects);
+					self.draw_calls += 1;
+				},
+				BatchKey::Copy(tex_handle, tint) => {
+					let tint = unpack_color(tint);
+					let texture = &mut self.texture_list[tex_handle];
+					texture.set_color_mod(tint.r, tint.g, tint.b);
+					texture.set_alpha_mod(tint.a);
+					let texture = &self.texture_list[tex_handle];
+					for cmd in commands[pos..end].iter() {
+						let _ = match cmd.op {
+							RenderOp::Copy(src, dst) => self.canvas.copy(texture, src, dst),
+							RenderOp::CopyEx(src, dst, angle, center, flip_x, flip_y) => {
+								self.canvas.copy_ex(texture, src, dst, angle, center, flip_x, flip_y)
+							},
+							RenderOp::Fill(_) => Ok(())
+						};
+						self.draw_calls += 1;
+					}
+					let texture = &mut self.texture_list[tex_handle];
+					texture.set_color_mod(255, 255, 255);
+					texture.set_alpha_mod(255);
+				}
+			}
+			pos = end;
+		}
+
+		commands.clear();
+		self.commands = commands;
+	}
+
+	// многоугольник из одного или нескольких контуров; с aa края сглажены
+	pub fn fill_polygon(
+		&mut self, contours: &[&[(f32, f32)]], rule: FillRule, color: Color, aa: bool
 	) {
-    	if y0 > y1 {
-        	let mut tmp = y0;
-        	y0 = y1;
-        	y1 = tmp;
-        	tmp = x0;
-        	x0 = x1;
-        	x1 = tmp;
-    	}
-
-    	if y0 > y2 {
-        	let mut tmp = y0;
-        	y0 = y2;
-        	y2 = tmp;
-        	tmp = x0;
-        	x0 = x2;
-        	x2 = tmp;
-    	}
-
-    	if y1 > y2 {
-        	let mut tmp = y1;
-        	y1 = y2;
-        	y2 = tmp;
-        	tmp = x1;
-        	x1 = x2;
-        	x2 = tmp;
-    	}
-
-	    let mut dx1 = x1 - x0;
-    	let mut dy1 = y1 - y0;
-	    let dx2 = x2 - x0;
-	    let dy2 = y2 - y0;
-
-    	let mut top_y = y0;
-
-	    while top_y < y1 {
-    	    let cross_x1 = x0 + dx1 * (top_y - y0) / dy1;
-	        let cross_x2 = x0 + dx2 * (top_y - y0) / dy2;
-	        if cross_x1 > cross_x2 {
-				self.draw_rect(cross_x2, top_y, (cross_x1 - cross_x2) as u32, 1, color);
-	        } else {
-	            self.draw_rect(cross_x1, top_y, (cross_x2 - cross_x1) as u32, 1, color);
-	        }
-	        top_y += 1;
-    	}
-
-	    dx1 = x2 - x1;
-	    dy1 = y2 - y1;
-	    while top_y < y2 {
-    	    let cross_x1 = x1 + dx1 * (top_y - y1) / dy1;
-	        let cross_x2 = x0 + dx2 * (top_y - y0) / dy2;
-    	    if cross_x1 > cross_x2 {
-        	    self.draw_rect(cross_x2, top_y, (cross_x1 - cross_x2) as u32, 1, color);
-        	} else {
-            	self.draw_rect(cross_x1, top_y, (cross_x2 - cross_x1) as u32, 1, color);
-        	}
-	        top_y += 1;
-    	}
+		let mut spans = std::mem::take(&mut self.spans);
+		spans.clear();
+		if aa {
+			raster::fill_aa(contours, rule, &mut spans);
+		} else {
+			raster::fill(contours, rule, &mut spans);
+		}
+		for span in spans.iter() {
+			let a = (color.a as u32 * span.alpha as u32 / 255) as u8;
+			self.draw_rect(
+				span.x0, span.y, (span.x1 - span.x0) as u32, 1,
+				Color::RGBA(color.r, color.g, color.b, a)
+			);
+		}
+		self.spans = spans;
+	}
+
+	// отрезок от p0 до p1
+	pub fn draw_line(
+		&mut self, p0: (f32, f32), p1: (f32, f32), width: f32, color: Color, aa: bool
+	) {
+		let quad = raster::line(p0.0, p0.1, p1.0, p1.1, width);
+		self.fill_polygon(&[&quad], FillRule::NONZERO, color, aa);
+	}
+
+	pub fn fill_circle(&mut self, cx: f32, cy: f32, r: f32, color: Color, aa: bool) {
+		let points = raster::circle(cx, cy, r);
+		self.fill_polygon(&[&points], FillRule::NONZERO, color, aa);
+	}
+
+	// кольцо толщиной width внутрь от радиуса r
+	pub fn draw_ring(
+		&mut self, cx: f32, cy: f32, r: f32, width: f32, color: Color, aa: bool
+	) {
+		let outer = raster::circle(cx, cy, r);
+		let inner = raster::circle(cx, cy, (r - width).max(0.0));
+		self.fill_polygon(&[&outer, &inner], FillRule::EVENODD, color, aa);
+	}
+}
+
+// отражение, масштаб и поворот (градусы против часовой) вокруг pivot,
+// а также оттенок, прозрачность и белая вспышка
+#[derive(Copy, Clone)]
+pub struct BitmapTransform {
+	pub flip_x: bool,
+	pub flip_y: bool,
+	pub scale: f32,
+	pub angle: f32,
+	pub pivot: (i32, i32),
+	pub tint: Color,
+	pub flash: bool
+}
+
+impl BitmapTransform {
+	pub const IDENTITY: BitmapTransform = BitmapTransform {
+		flip_x: false, flip_y: false, scale: 1.0, angle: 0.0, pivot: (0, 0),
+		tint: Color::RGBA(255, 255, 255, 255), flash: false
+	};
+
+	pub fn from_sto(sto: &StageObject, pivot: (i32, i32)) -> Self {
+		BitmapTransform {
+			flip_x: sto.flip_x,
+			flip_y: sto.flip_y,
+			scale: sto.scale,
+			angle: sto.angle as f32,
+			pivot: pivot,
+			tint: Color::RGBA(sto.tint.r, sto.tint.g, sto.tint.b, sto.alpha),
+			flash: sto.flash > 0
+		}
+	}
+
+	pub fn is_identity(&self) -> bool {
+		!self.flip_x && !self.flip_y && self.scale == 1.0 && self.angle == 0.0
 	}
 }
 
 pub trait Drawable {
 	fn draw(&self, sto: &StageObject, renderer: &mut Renderer);
+
+	// шаг анимации; возвращает функцию, которую надо вызвать по окончании цикла
+	fn animate(&self, state: &mut AnimState) -> Option<AnimCallback> {
+		None
+	}
+}
+
+fn pack_color(color: Color) -> u32 {
+	(color.r as u32) << 24 | (color.g as u32) << 16 | (color.b as u32) << 8 | color.a as u32
+}
+
+fn unpack_color(color: u32) -> Color {
+	Color::RGBA((color >> 24) as u8, (color >> 16) as u8, (color >> 8) as u8, color as u8)
+}
+
+// ссылка на созданный во время работы drawable; после remove
+// поколение слота меняется, и старая ссылка перестаёт действовать
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct DrawableHandle {
+	index: usize,
+	generation: u32
+}
+
+// что рисует StageObject: статический drawable или запись реестра
+#[derive(Copy, Clone)]
+pub enum DrawableRef {
+	STATIC(&'static dyn Drawable),
+	HANDLE(DrawableHandle)
+}
+
+impl<T: Drawable + 'static> From<&'static T> for DrawableRef {
+	fn from(drawable: &'static T) -> Self {
+		DrawableRef::STATIC(drawable)
+	}
+}
+
+impl From<DrawableHandle> for DrawableRef {
+	fn from(handle: DrawableHandle) -> Self {
+		DrawableRef::HANDLE(handle)
+	}
+}
+
+// drawable, созданные во время работы (составные картинки, взрывы по размеру объекта);
+// запись удаляет тот, кто её добавил, или сцена вместе с объектом-владельцем (StageObject::owned)
+#[derive(Default)]
+pub struct DrawableRegistry {
+	slots: Vec<(u32, Option<Box<dyn Drawable>>)>,
+	free: Vec<usize>
+}
+
+impl DrawableRegistry {
+	pub fn new() -> Self {
+		DrawableRegistry::default()
+	}
+
+	pub fn add(&mut self, drawable: Box<dyn Drawable>) -> DrawableHandle {
+		let index = match self.free.pop() {
+			Some(index) => index,
+			None => {
+				self.slots.push((0, None));
+				self.slots.len() - 1
+			}
+		};
+		let slot = &mut self.slots[index];
+		slot.0 = slot.0.wrapping_add(1);
+		slot.1 = Some(drawable);
+		DrawableHandle { index: index, generation: slot.0 }
+	}
+
+	// повторное удаление ничего не делает
+	pub fn remove(&mut self, handle: DrawableHandle) {
+		if self.get(handle).is_some() {
+			let slot = &mut self.slots[handle.index];
+			slot.0 = slot.0.wrapping_add(1);
+			slot.1 = None;
+			self.free.push(handle.index);
+		}
+	}
+
+	pub fn get(&self, handle: DrawableHandle) -> Option<&dyn Drawable> {
+		match self.slots.get(handle.index) {
+			Some((generation, Some(drawable))) if *generation == handle.generation => {
+				Some(drawable.as_ref())
+			},
+			_ => None
+		}
+	}
+
+	// None, если запись уже удалена
+	pub fn resolve(&self, drawable: DrawableRef) -> Option<&dyn Drawable> {
+		match drawable {
+			DrawableRef::STATIC(drawable) => Some(drawable),
+			DrawableRef::HANDLE(handle) => self.get(handle)
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.slots.len() - self.free.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum AnimMode {
+	LOOP, ONCE, PINGPONG
+}
+
+#[derive(Copy, Clone)]
+pub struct AnimFrame {
+	pub rect: PlainRect,
+	pub duration: u32,		// в шагах симуляции
+	pub offset: (i32, i32)	// сдвиг кадра; при flip_x dx отражается
+}
+
+// общее описание анимации; текущий кадр хранит каждый StageObject в AnimState
+pub struct DrawableAnimation {
+	pub tex_handle: usize,
+	pub frames: &'static [AnimFrame],
+	pub mode: AnimMode,
+	pub pivot: (i32, i32),
+	pub on_done: Option<AnimCallback>	// для LOOP и PINGPONG - после каждого цикла
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct AnimState {
+	pub frame: usize,
+	pub time: u32,
+	pub reverse: bool,
+	pub done: bool
+}
+
+impl AnimState {
+	pub fn new() -> Self {
+		AnimState::default()
+	}
+
+	// шаг по frame_cnt кадрам, текущий длится duration шагов;
+	// true, когда закончился цикл (для ONCE - вся анимация)
+	fn step(&mut self, mode: AnimMode, frame_cnt: usize, duration: u32) -> bool {
+		if self.done {
+			return false;
+		}
+		self.time += 1;
+		if self.time < duration {
+			return false;
+		}
+		self.time = 0;
+
+		let last = frame_cnt - 1;
+		match mode {
+			AnimMode::LOOP => {
+				self.frame = if self.frame >= last { 0 } else { self.frame + 1 };
+				self.frame == 0
+			},
+			AnimMode::ONCE => {
+				if self.frame >= last {
+					self.done = true;
+				} else {
+					self.frame += 1;
+				}
+				self.done
+			},
+			AnimMode::PINGPONG => {
+				if last == 0 {
+					true
+				} else if self.reverse {
+					self.frame -= 1;
+					self.reverse = self.frame > 0;
+					self.frame == 0
+				} else {
+					self.frame += 1;
+					self.reverse = self.frame == last;
+					false
+				}
+			}
+		}
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct RectFrame {
+	pub rects: &'static [ColorRect],
+	pub duration: u32
+}
+
+// анимация из цветных прямоугольников относительно позиции объекта;
+// при flip_x кадры отражаются по ширине w
+pub struct DrawableRectAnimation {
+	pub frames: &'static [RectFrame],
+	pub mode: AnimMode,
+	pub w: u32,
+	pub on_done: Option<AnimCallback>
+}
+
+// несколько drawable на одном объекте, рисуются по порядку; состояние
+// анимации у объекта одно, поэтому анимированной может быть только одна часть
+pub struct DrawableGroup {
+	pub parts: Vec<&'static dyn Drawable>
+}
+
+// кольцо с центром в позиции объекта: за duration шагов
+// растёт до радиуса r, становится тоньше и гаснет
+pub struct DrawableExplosion {
+	pub color: Color,
+	pub r: f32,
+	pub duration: u32,
+	pub on_done: Option<AnimCallback>
 }
 
 pub struct DrawableBitmap {
 	pub tex_handle: usize,
-	pub rect: PlainRect
+	pub rect: PlainRect,
+	pub pivot: (i32, i32)	// центр поворота и масштаба относительно спрайта
 }
 
 pub struct DrawableRect {
@@ -139,13 +518,82 @@ pub struct DrawableListRect {
 
 impl Drawable for DrawableBitmap {
 	fn draw(&self, sto: &StageObject, renderer: &mut Renderer) {
-		renderer.draw_bitmap(sto.x, sto.y, self.tex_handle, self.rect);
+		renderer.draw_bitmap(
+			sto.x, sto.y, self.tex_handle, self.rect,
+			&BitmapTransform::from_sto(sto, self.pivot)
+		);
+	}
+}
+
+impl Drawable for DrawableAnimation {
+	fn draw(&self, sto: &StageObject, renderer: &mut Renderer) {
+		let frame = self.frames[sto.anim.frame.min(self.frames.len() - 1)];
+		let dx = if sto.flip_x { -frame.offset.0 } else { frame.offset.0 };
+		renderer.draw_bitmap(
+			sto.x + dx, sto.y + frame.offset.1, self.tex_handle, frame.rect,
+			&BitmapTransform::from_sto(sto, self.pivot)
+		);
+	}
+
+	fn animate(&self, state: &mut AnimState) -> Option<AnimCallback> {
+		let duration = self.frames[state.frame.min(self.frames.len() - 1)].duration;
+		if state.step(self.mode, self.frames.len(), duration) { self.on_done } else { None }
+	}
+}
+
+impl Drawable for DrawableRectAnimation {
+	fn draw(&self, sto: &StageObject, renderer: &mut Renderer) {
+		let frame = self.frames[sto.anim.frame.min(self.frames.len() - 1)];
+		for rect in frame.rects.iter() {
+			let x = if sto.flip_x { self.w as i32 - rect.x - rect.w as i32 } else { rect.x };
+			renderer.draw_rect(sto.x + x, sto.y + rect.y, rect.w, rect.h, sto.modulate(rect.color));
+		}
+	}
+
+	fn animate(&self, state: &mut AnimState) -> Option<AnimCallback> {
+		let duration = self.frames[state.frame.min(self.frames.len() - 1)].duration;
+		if state.step(self.mode, self.frames.len(), duration) { self.on_done } else { None }
+	}
+}
+
+impl Drawable for DrawableGroup {
+	fn draw(&self, sto: &StageObject, renderer: &mut Renderer) {
+		for part in self.parts.iter() {
+			part.draw(sto, renderer);
+		}
+	}
+
+	fn animate(&self, state: &mut AnimState) -> Option<AnimCallback> {
+		self.parts.iter().find_map(|part| part.animate(state))
+	}
+}
+
+impl Drawable for DrawableExplosion {
+	fn draw(&self, sto: &StageObject, renderer: &mut Renderer) {
+		let t = (sto.anim.time as f32 / self.duration as f32).min(1.0);
+		let r = self.r * (0.3 + 0.7 * t);
+		let width = (self.r * 0.5 * (1.0 - t)).max(1.0);
+		let mut color = sto.modulate(self.color);
+		color.a = (color.a as f32 * (1.0 - t)) as u8;
+		renderer.draw_ring(sto.x as f32, sto.y as f32, r, width, color, true);
+	}
+
+	fn animate(&self, state: &mut AnimState) -> Option<AnimCallback> {
+		if state.done {
+			return None;
+		}
+		state.time += 1;
+		if state.time < self.duration {
+			return None;
+		}
+		state.done = true;
+		self.on_done
 	}
 }
 
 impl Drawable for DrawableRect {
 	fn draw(&self, sto: &StageObject, renderer: &mut Renderer) {
-		renderer.draw_rect(sto.x, sto.y, self.w, self.h, self.color);
+		renderer.draw_rect(sto.x, sto.y, self.w, self.h, sto.modulate(self.color));
 	}
 }
 
@@ -153,50 +601,75 @@ impl Drawable for DrawableListRect {
 	fn draw(&self, sto: &StageObject, renderer: &mut Renderer) {
 		for i in 0..self.cnt {
 			let rect = self.list_rect[i];
-			renderer.draw_rect(sto.x + rect.x, sto.y + rect.y, rect.w, rect.h, rect.color);
+			renderer.draw_rect(
+				sto.x + rect.x, sto.y + rect.y, rect.w, rect.h, sto.modulate(rect.color)
+			);
 		}
 	}
 }
 
 impl Drawable for DrawableRotRect {
 	fn draw(&self, sto: &StageObject, renderer: &mut Renderer) {
-		let theta: f32 = (sto.angle as f32) * 3.1415926 / 180.0;
+		let theta: f32 = (sto.angle as f32).to_radians();
 		let cos = theta.cos();
 		let sin = theta.sin();
 
-		let cx = self.cx;
-		let cy = self.cy;
-		let x0 = -cx;
-		let y0 = cy;
-		let mut x1 = self.w as i32 - cx;
-		let mut y1 = cy;
-		let x2 = self.w as i32 - cx;
-		let y2 = cy - self.h as i32;
-
-		let cxx = sto.x;
-		let cyy = sto.y;
-
-		renderer.rasterize_poly(
-			cxx + (x0 as f32 * cos - y0 as f32 * sin).round() as i32,
-			cyy - (x0 as f32 * sin + y0 as f32 * cos).round() as i32,
-			cxx + (x1 as f32 * cos - y1 as f32 * sin).round() as i32,
-			cyy - (x1 as f32 * sin + y1 as f32 * cos).round() as i32,
-			cxx + (x2 as f32 * cos - y2 as f32 * sin).round() as i32,
-			cyy - (x2 as f32 * sin + y2 as f32 * cos).round() as i32,
-			self.color
-		);
-		
-		x1 = x0;
-		y1 = y2;
-
-		renderer.rasterize_poly(
-			cxx + (x0 as f32 * cos - y0 as f32 * sin).round() as i32,
-			cyy - (x0 as f32 * sin + y0 as f32 * cos).round() as i32,
-			cxx + (x1 as f32 * cos - y1 as f32 * sin).round() as i32,
-			cyy - (x1 as f32 * sin + y1 as f32 * cos).round() as i32,
-			cxx + (x2 as f32 * cos - y2 as f32 * sin).round() as i32,
-			cyy - (x2 as f32 * sin + y2 as f32 * cos).round() as i32,
-			self.color
+		// углы относительно центра поворота, ось y вверх
+		let cx = self.cx as f32;
+		let cy = self.cy as f32;
+		let w = self.w as f32;
+		let h = self.h as f32;
+		let corners = [(-cx, cy), (w - cx, cy), (w - cx, cy - h), (-cx, cy - h)];
+
+		let x = sto.x as f32;
+		let y = sto.y as f32;
+		let mut points = [(0.0, 0.0); 4];
+		for i in 0..4 {
+			let (px, py) = corners[i];
+			points[i] = (x + px * cos - py * sin, y - (px * sin + py * cos));
+		}
+		renderer.fill_polygon(&[&points], FillRule::NONZERO, sto.modulate(self.color), false);
+	}
+}
+
+// трассер: отрезок длиной len от позиции объекта по направлению sto.angle
+pub struct DrawableLine {
+	pub color: Color,
+	pub len: f32,
+	pub width: f32,
+	pub aa: bool
+}
+
+impl Drawable for DrawableLine {
+	fn draw(&self, sto: &StageObject, renderer: &mut Renderer) {
+		let theta: f32 = (sto.angle as f32).to_radians();
+		let len = self.len * sto.scale;
+		let x = sto.x as f32;
+		let y = sto.y as f32;
+		renderer.draw_line(
+			(x, y), (x + len * theta.cos(), y - len * theta.sin()),
+			self.width, sto.modulate(self.color), self.aa
 		);
 	}
 }
+
+// круг (width 0) или кольцо с центром в позиции объекта;
+// радиус умножается на sto.scale, так что ударная волна растёт через масштаб
+pub struct DrawableCircle {
+	pub color: Color,
+	pub r: f32,
+	pub width: f32,
+	pub aa: bool
+}
+
+impl Drawable for DrawableCircle {
+	fn draw(&self, sto: &StageObject, renderer: &mut Renderer) {
+		let r = self.r * sto.scale;
+		let color = sto.modulate(self.color);
+		if self.width > 0.0 {
+			renderer.draw_ring(sto.x as f32, sto.y as f32, r, self.width, color, self.aa);
+		} else {
+			renderer.fill_circle(sto.x as f32, sto.y as f32, r, color, self.aa);
+		}
+	}
+}
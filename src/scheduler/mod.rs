@@ -0,0 +1,435 @@
+use crate::xrand::XRand;
+use crate::Context;
+
+// задержка таймера: случайная в диапазоне min..=max, в кадрах или миллисекундах
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TimerDelay {
+	FRAMES(u32, u32),
+	MS(u32, u32)
+}
+
+impl TimerDelay {
+	pub fn frames(cnt: u32) -> Self {
+		TimerDelay::FRAMES(cnt, cnt)
+	}
+
+	pub fn ms(ms: u32) -> Self {
+		TimerDelay::MS(ms, ms)
+	}
+}
+
+// ссылка на таймер; после срабатывания одноразового таймера или отмены
+// поколение слота меняется, и старая ссылка перестаёт действовать
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct TimerHandle {
+	index: u32,
+	generation: u32
+}
+
+impl TimerHandle {
+	// поколение 0 не выдаётся: таймер не заведён
+	pub const NONE: TimerHandle = TimerHandle { index: 0, generation: 0 };
+}
+
+pub type TimerCallback = fn(ctx: &mut Context, handle: TimerHandle, arg: u32);
+
+// только простые данные, чтобы таймеры можно было сохранить
+#[derive(Copy, Clone)]
+struct Timer {
+	generation: u32,
+	active: bool,
+	fired: bool,
+	due: u64,
+	period_min: u32,		// 0 - одноразовый
+	period_max: u32,
+	callback: u32,			// NO_CALLBACK или номер из register
+	arg: u32
+}
+
+const NO_CALLBACK: u32 = u32::MAX;
+const TIMER_BYTES: usize = 4 + 1 + 1 + 8 + 4 + 4 + 4 + 4;
+
+// таймеры в кадрах симуляции; сработавший таймер выставляет флаг,
+// который опрашивает поведение, либо вызывает зарегистрированную функцию
+pub struct Scheduler {
+	frame: u64,
+	frame_ms: u32,
+	timers: Vec<Timer>,
+	free: Vec<usize>,
+	callbacks: Vec<TimerCallback>,
+	fired_callbacks: Vec<TimerHandle>
+}
+
+impl Scheduler {
+	pub fn create(frame_ms: u32, capacity: usize) -> Self {
+		Scheduler {
+			frame: 0,
+			frame_ms: frame_ms,
+			timers: Vec::with_capacity(capacity),
+			free: Vec::with_capacity(capacity),
+			callbacks: Vec::new(),
+			fired_callbacks: Vec::with_capacity(capacity)
+		}
+	}
+
+	pub fn frame(&self) -> u64 {
+		self.frame
+	}
+
+	// номер функции сохраняется вместо указателя, поэтому порядок
+	// регистрации должен быть одинаковым при каждом запуске
+	pub fn register(&mut self, callback: TimerCallback) -> u32 {
+		self.callbacks.push(callback);
+		(self.callbacks.len() - 1) as u32
+	}
+
+	pub fn once(&mut self, rand: &mut XRand, delay: TimerDelay) -> TimerHandle {
+		let frames = self.to_frames(rand, delay);
+		self.add(frames, 0, 0)
+	}
+
+	// первое срабатывание через first, затем каждые period
+	pub fn repeat(
+		&mut self, rand: &mut XRand, first: TimerDelay, period: TimerDelay
+	) -> TimerHandle {
+		let frames = self.to_frames(rand, first);
+		let (min, max) = self.to_frame_range(period);
+		self.add(frames, min.max(1), max.max(1))
+	}
+
+	pub fn set_callback(&mut self, handle: TimerHandle, callback: u32, arg: u32) {
+		if callback as usize >= self.callbacks.len() {
+			panic!("set_callback: unknown callback {}", callback);
+		}
+		if let Some(timer) = self.find_mut(handle) {
+			timer.callback = callback;
+			timer.arg = arg;
+		}
+	}
+
+	// повторная отмена и отмена сработавшего таймера ничего не делают
+	pub fn cancel(&mut self, handle: TimerHandle) {
+		if self.find_mut(handle).is_some() {
+			self.release(handle.index as usize);
+		}
+	}
+
+	pub fn is_active(&self, handle: TimerHandle) -> bool {
+		self.find(handle).is_some()
+	}
+
+	// кадров до срабатывания; None для недействительной ссылки
+	pub fn remaining(&self, handle: TimerHandle) -> Option<u64> {
+		self.find(handle).map(|timer| timer.due - self.frame)
+	}
+
+	// забрать флаг срабатывания; одноразовый таймер после этого освобождается
+	pub fn take(&mut self, handle: TimerHandle) -> bool {
+		let (fired, once) = match self.find_mut(handle) {
+			Some(timer) if timer.fired => {
+				timer.fired = false;
+				(true, timer.period_min == 0)
+			},
+			_ => (false, false)
+		};
+		if fired && once {
+			self.release(handle.index as usize);
+		}
+		fired
+	}
+
+	// следующий кадр: сработавшие таймеры выставляют флаг,
+	// повторяющиеся перезаводятся с новой случайной задержкой
+	pub fn tick(&mut self, rand: &mut XRand) {
+		self.frame += 1;
+		for index in 0..self.timers.len() {
+			let timer = self.timers[index];
+			if !timer.active || timer.due > self.frame {
+				continue;
+			}
+			let handle = TimerHandle { index: index as u32, generation: timer.generation };
+			if timer.callback != NO_CALLBACK {
+				self.fired_callbacks.push(handle);
+			} else {
+				self.timers[index].fired = true;
+			}
+			if timer.period_min > 0 {
+				let period = rand.randint(timer.period_min, timer.period_max + 1)
+					.min(timer.period_max);
+				self.timers[index].due = self.frame + period as u64;
+			} else if timer.callback != NO_CALLBACK {
+				// одноразовый таймер с функцией никто не опрашивает
+				self.timers[index].due = u64::MAX;
+			}
+		}
+	}
+
+	pub fn clear(&mut self) {
+		for index in 0..self.timers.len() {
+			if self.timers[index].active {
+				self.release(index);
+			}
+		}
+		self.fired_callbacks.clear();
+		self.frame = 0;
+	}
+
+	pub fn active_cnt(&self) -> usize {
+		self.timers.iter().filter(|timer| timer.active).count()
+	}
+
+	pub fn save(&self, out: &mut Vec<u8>) {
+		out.extend_from_slice(&self.frame.to_le_bytes());
+		out.extend_from_slice(&self.frame_ms.to_le_bytes());
+		out.extend_from_slice(&(self.timers.len() as u32).to_le_bytes());
+		for timer in self.timers.iter() {
+			out.extend_from_slice(&timer.generation.to_le_bytes());
+			out.push(timer.active as u8);
+			out.push(timer.fired as u8);
+			out.extend_from_slice(&timer.due.to_le_bytes());
+			out.extend_from_slice(&timer.period_min.to_le_bytes());
+			out.extend_from_slice(&timer.period_max.to_le_bytes());
+			out.extend_from_slice(&timer.callback.to_le_bytes());
+			out.extend_from_slice(&timer.arg.to_le_bytes());
+		}
+	}
+
+	// возвращает число прочитанных байт
+	pub fn load(&mut self, data: &[u8]) -> usize {
+		let mut pos = 0;
+		self.frame = read_u64(data, &mut pos);
+		self.frame_ms = read_u32(data, &mut pos);
+		let cnt = read_u32(data, &mut pos) as usize;
+		if data.len() < pos + cnt * TIMER_BYTES {
+			panic!("scheduler: truncated save, {} timers expected", cnt);
+		}
+		self.timers.clear();
+		self.free.clear();
+		self.fired_callbacks.clear();
+		for index in 0..cnt {
+			let timer = Timer {
+				generation: read_u32(data, &mut pos),
+				active: read_u8(data, &mut pos) != 0,
+				fired: read_u8(data, &mut pos) != 0,
+				due: read_u64(data, &mut pos),
+				period_min: read_u32(data, &mut pos),
+				period_max: read_u32(data, &mut pos),
+				callback: read_u32(data, &mut pos),
+				arg: read_u32(data, &mut pos)
+			};
+			if timer.callback != NO_CALLBACK && timer.callback as usize >= self.callbacks.len() {
+				panic!("scheduler: callback {} is not registered", timer.callback);
+			}
+			if !timer.active {
+				self.free.push(index);
+			}
+			self.timers.push(timer);
+		}
+		pos
+	}
+
+	fn add(&mut self, frames: u32, period_min: u32, period_max: u32) -> TimerHandle {
+		let index = match self.free.pop() {
+			Some(index) => index,
+			None => {
+				self.timers.push(Timer {
+					generation: 0, active: false, fired: false, due: 0,
+					period_min: 0, period_max: 0, callback: NO_CALLBACK, arg: 0
+				});
+				self.timers.len() - 1
+			}
+		};
+		let timer = &mut self.timers[index];
+		timer.generation = timer.generation.wrapping_add(1).max(1);
+		timer.active = true;
+		timer.fired = false;
+		timer.due = self.frame + frames.max(1) as u64;
+		timer.period_min = period_min;
+		timer.period_max = period_max;
+		timer.callback = NO_CALLBACK;
+		timer.arg = 0;
+		TimerHandle { index: index as u32, generation: timer.generation }
+	}
+
+	fn release(&mut self, index: usize) {
+		let timer = &mut self.timers[index];
+		timer.active = false;
+		timer.fired = false;
+		timer.generation = timer.generation.wrapping_add(1).max(1);
+		self.free.push(index);
+	}
+
+	fn find(&self, handle: TimerHandle) -> Option<&Timer> {
+		self.timers.get(handle.index as usize)
+			.filter(|timer| timer.active && timer.generation == handle.generation)
+	}
+
+	fn find_mut(&mut self, handle: TimerHandle) -> Option<&mut Timer> {
+		self.timers.get_mut(handle.index as usize)
+			.filter(|timer| timer.active && timer.generation == handle.generation)
+	}
+
+	fn to_frame_range(&self, delay: TimerDelay) -> (u32, u32) {
+		let (min, max) = match delay {
+			TimerDelay::FRAMES(min, max) => (min, max),
+			TimerDelay::MS(min, max) => (min.div_ceil(self.frame_ms), max.div_ceil(self.frame_ms))
+		};
+		(min, max.max(min))
+	}
+
+	fn to_frames(&self, rand: &mut XRand, delay: TimerDelay) -> u32 {
+		let (min, max) = self.to_frame_range(delay);
+		if min == max {
+			return min;
+		}
+		rand.randint(min, max + 1).min(max)
+	}
+}
+
+// вызвать функции сработавших таймеров; функция может заводить новые таймеры
+pub fn run_callbacks(ctx: &mut Context) {
+	let mut fired = std::mem::take(&mut ctx.scheduler.fired_callbacks);
+	for handle in fired.drain(..) {
+		let timer = match ctx.scheduler.find(handle) {
+			Some(timer) => *timer,
+			None => continue		// отменён предыдущей функцией
+		};
+		if timer.period_min == 0 {
+			ctx.scheduler.release(handle.index as usize);
+		}
+		let callback = ctx.scheduler.callbacks[timer.callback as usize];
+		callback(ctx, handle, timer.arg);
+	}
+	ctx.scheduler.fired_callbacks = fired;
+}
+
+pub fn tick(ctx: &mut Context) {
+	ctx.scheduler.tick(&mut ctx.rand);
+	run_callbacks(ctx);
+}
+
+fn read_u8(data: &[u8], pos: &mut usize) -> u8 {
+	let value = data[*pos];
+	*pos += 1;
+	value
+}
+
+fn read_u32(data: &[u8], pos: &mut usize) -> u32 {
+	let mut bytes = [0u8; 4];
+	bytes.copy_from_slice(&data[*pos..*pos + 4]);
+	*pos += 4;
+	u32::from_le_bytes(bytes)
+}
+
+fn read_u64(data: &[u8], pos: &mut usize) -> u64 {
+	let mut bytes = [0u8; 8];
+	bytes.copy_from_slice(&data[*pos..*pos + 8]);
+	*pos += 8;
+	u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::event::GameEventKind;
+	use crate::game::GmoType;
+
+	// номера сработавших за кадр таймеров, кадр за кадром
+	fn run(
+		scheduler: &mut Scheduler, rand: &mut XRand, handles: &[TimerHandle], frames: u32
+	) -> Vec<Vec<usize>> {
+		let mut fired = Vec::new();
+		for _ in 0..frames {
+			scheduler.tick(rand);
+			fired.push((0..handles.len()).filter(|&i| scheduler.take(handles[i])).collect());
+		}
+		fired
+	}
+
+	#[test]
+	fn once_fires_once_and_repeat_keeps_firing() {
+		let mut rand = XRand::from_seed(1);
+		let mut scheduler = Scheduler::create(33, 8);
+		let once = scheduler.once(&mut rand, TimerDelay::frames(3));
+		let repeat = scheduler.repeat(&mut rand, TimerDelay::frames(2), TimerDelay::frames(4));
+		let fired = run(&mut scheduler, &mut rand, &[once, repeat], 10);
+		let frames_of = |i: usize| (0..fired.len()).filter(|&f| fired[f].contains(&i)).collect::<Vec<_>>();
+		assert_eq!(frames_of(0), vec![2]);
+		assert_eq!(frames_of(1), vec![1, 5, 9]);
+		assert!(!scheduler.is_active(once));
+		assert!(scheduler.is_active(repeat));
+	}
+
+	#[test]
+	fn jitter_stays_in_range() {
+		let mut rand = XRand::from_seed(7);
+		let mut scheduler = Scheduler::create(10, 8);
+		let mut seen = Vec::new();
+		for _ in 0..200 {
+			let handle = scheduler.once(&mut rand, TimerDelay::MS(25, 50));
+			let frames = scheduler.remaining(handle).unwrap();
+			assert!((3..=5).contains(&frames), "{} frames", frames);
+			seen.push(frames);
+			scheduler.cancel(handle);
+		}
+		assert!(seen.contains(&3) && seen.contains(&5));
+	}
+
+	#[test]
+	fn cancel_invalidates_handle() {
+		let mut rand = XRand::from_seed(1);
+		let mut scheduler = Scheduler::create(33, 8);
+		let handle = scheduler.once(&mut rand, TimerDelay::frames(1));
+		scheduler.cancel(handle);
+		scheduler.cancel(handle);
+		assert_eq!(scheduler.active_cnt(), 0);
+		// слот переиспользуется, но старая ссылка на него не действует
+		let other = scheduler.once(&mut rand, TimerDelay::frames(1));
+		scheduler.tick(&mut rand);
+		assert!(!scheduler.take(handle));
+		assert!(scheduler.take(other));
+	}
+
+	#[test]
+	fn save_load_fires_same_timers() {
+		let mut rand = XRand::from_seed(3);
+		let mut scheduler = Scheduler::create(33, 8);
+		let handles = [
+			scheduler.once(&mut rand, TimerDelay::FRAMES(5, 15)),
+			scheduler.repeat(&mut rand, TimerDelay::frames(1), TimerDelay::FRAMES(2, 6)),
+			scheduler.repeat(&mut rand, TimerDelay::MS(50, 300), TimerDelay::MS(100, 200))
+		];
+		run(&mut scheduler, &mut rand, &handles, 4);
+
+		let mut saved = Vec::new();
+		scheduler.save(&mut saved);
+		rand.save(&mut saved);
+		let expected = run(&mut scheduler, &mut rand, &handles, 50);
+
+		let mut loaded = Scheduler::create(33, 8);
+		let mut loaded_rand = XRand::from_seed(0);
+		let pos = loaded.load(&saved);
+		assert_eq!(loaded_rand.load(&saved[pos..]), 16);
+		assert_eq!(loaded.frame(), 4);
+		assert_eq!(run(&mut loaded, &mut loaded_rand, &handles, 50), expected);
+	}
+
+	fn push_hit(ctx: &mut Context, handle: TimerHandle, arg: u32) {
+		ctx.events.push(GameEventKind::GunHit, GmoType::NONE, arg as f32, 0.0);
+	}
+
+	#[test]
+	fn callback_runs_on_tick() {
+		let mut ctx = Context::for_tests(8);
+		let callback = ctx.scheduler.register(push_hit);
+		let handle = ctx.scheduler.repeat(&mut ctx.rand, TimerDelay::frames(1), TimerDelay::frames(2));
+		ctx.scheduler.set_callback(handle, callback, 42);
+		for _ in 0..5 {
+			tick(&mut ctx);
+		}
+		assert_eq!(ctx.events.len(), 3);
+		assert!(ctx.events.iter().all(|e| e.kind == GameEventKind::GunHit && e.x == 42.0));
+		// флаг у таймера с функцией не выставляется
+		assert!(!ctx.scheduler.take(handle));
+	}
+}
@@ -1,7 +1,11 @@
 use sdl2::pixels::Color;
 
-use crate::renderer::{ DrawableBitmap, DrawableRect, DrawableListRect, DrawableRotRect, ColorRect };
-use crate::game::PlainRect;
+use crate::renderer::{
+	DrawableRect, DrawableListRect, DrawableRotRect, DrawableRectAnimation,
+	RectFrame, ColorRect, AnimMode
+};
+
+// спрайты атласа описаны в rds_atlas.txt (модуль atlas)
 
 pub static DR_SHOT: DrawableRect = DrawableRect {
 	w: 3, h: 3, color: Color::RGB(255, 255, 255)
@@ -15,16 +19,6 @@ pub static DR_SHAFT: DrawableRotRect = DrawableRotRect {
 	w:20, h:6, cx: 0, cy: 3, color: Color::RGB(0, 0, 255)
 };
 
-pub static DR_CARRIER_LEFT: DrawableBitmap = DrawableBitmap {
-	tex_handle: 0,
-	rect: PlainRect { x: 0, y: 188, w: 101, h: 50 }
-};
-
-pub static DR_CARRIER_RIGHT: DrawableBitmap = DrawableBitmap {
-	tex_handle: 0,
-	rect: PlainRect { x: 102, y: 188, w: 101, h: 50 }
-};
-
 pub static DR_GUN: DrawableListRect = DrawableListRect {
 	cnt: 4,
 	list_rect: [
@@ -40,26 +34,39 @@ pub static DR_BOMBER: DrawableRect = DrawableRect {
 	w: 30, h: 20, color: Color::RGB(0, 0, 255)
 };
 
-pub static DR_LOGO: DrawableBitmap = DrawableBitmap {
-	tex_handle: 0,
-	rect: PlainRect { x: 0, y: 0, w: 630, h: 187 }
-};
-
-pub static DR_TROOPER: DrawableBitmap = DrawableBitmap {
-	tex_handle: 0,
-	rect: PlainRect { x: 246, y: 188, w: 17, h: 26 }
+pub static DR_BOMB: DrawableRect = DrawableRect {
+	w: 10, h: 10, color: Color::RGB(255, 0, 0)
 };
 
-pub static DR_CHUTE: DrawableBitmap = DrawableBitmap {
-	tex_handle: 0,
-	rect: PlainRect { x: 204, y: 188, w: 41, h: 51 }
-};
+// лопасти несущего и хвостового винта поверх спрайта carrier (101x50):
+// несущий винт поворачивается к зрителю и обратно, хвостовой - на четверть оборота
+const ROTOR_COLOR: Color = Color::RGB(70, 70, 70);
 
-pub static DR_FALLING: DrawableBitmap = DrawableBitmap {
-	tex_handle: 0,
-	rect: PlainRect { x: 264, y: 188, w: 16, h: 25 }
-};
-
-pub static DR_BOMB: DrawableRect = DrawableRect {
-	w: 10, h: 10, color: Color::RGB(255, 0, 0)
+pub static DR_ROTOR: DrawableRectAnimation = DrawableRectAnimation {
+	frames: &[
+		RectFrame {
+			rects: &[
+				ColorRect { x: 28, y: 2, w: 72, h: 2, color: ROTOR_COLOR },
+				ColorRect { x: 11, y: 8, w: 2, h: 19, color: ROTOR_COLOR }
+			],
+			duration: 1
+		},
+		RectFrame {
+			rects: &[
+				ColorRect { x: 46, y: 2, w: 38, h: 2, color: ROTOR_COLOR },
+				ColorRect { x: 10, y: 16, w: 4, h: 3, color: ROTOR_COLOR }
+			],
+			duration: 1
+		},
+		RectFrame {
+			rects: &[
+				ColorRect { x: 60, y: 2, w: 10, h: 2, color: ROTOR_COLOR },
+				ColorRect { x: 3, y: 16, w: 19, h: 2, color: ROTOR_COLOR }
+			],
+			duration: 1
+		}
+	],
+	mode: AnimMode::PINGPONG,
+	w: 101,
+	on_done: None
 };
@@ -0,0 +1,186 @@
+use std::any::{ Any, TypeId };
+use std::collections::HashMap;
+
+use crate::pantry::Pantry;
+
+// ссылка на данные поведения: тип хранилища и индекс в нём
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct BhvHandle {
+	pub type_id: TypeId,
+	pub index: usize
+}
+
+// все данные поведения одного объекта, по одной записи каждого типа
+#[derive(Clone)]
+pub struct BhvDataSet {
+	handles: Vec<BhvHandle>
+}
+
+impl BhvDataSet {
+	pub fn new(handles: &[BhvHandle]) -> Self {
+		BhvDataSet { handles: handles.to_vec() }
+	}
+
+	pub fn get<T: 'static>(&self) -> BhvHandle {
+		let type_id = TypeId::of::<T>();
+		match self.handles.iter().find(|h| h.type_id == type_id) {
+			Some(handle) => *handle,
+			None => panic!("no {} in behaviour data", std::any::type_name::<T>())
+		}
+	}
+}
+
+trait AnyPantry {
+	fn as_any(&self) -> &dyn Any;
+	fn as_any_mut(&mut self) -> &mut dyn Any;
+	fn free(&mut self, index: usize);
+	fn clear(&mut self);
+	fn used_indices(&self) -> Vec<usize>;
+	fn type_name(&self) -> &'static str;
+}
+
+impl<T: 'static> AnyPantry for Pantry<T> {
+	fn as_any(&self) -> &dyn Any {
+		self
+	}
+
+	fn as_any_mut(&mut self) -> &mut dyn Any {
+		self
+	}
+
+	fn free(&mut self, index: usize) {
+		Pantry::free(self, index);
+	}
+
+	fn clear(&mut self) {
+		Pantry::clear(self);
+	}
+
+	fn used_indices(&self) -> Vec<usize> {
+		Pantry::used_indices(self)
+	}
+
+	fn type_name(&self) -> &'static str {
+		std::any::type_name::<T>()
+	}
+}
+
+// хранилища данных поведения по типу; создаются при первом обращении
+pub struct Storage {
+	default_capacity: usize,
+	capacities: HashMap<TypeId, usize>,
+	pantries: HashMap<TypeId, Box<dyn AnyPantry>>
+}
+
+impl Storage {
+	pub fn create(default_capacity: usize) -> Self {
+		Storage {
+			default_capacity: default_capacity,
+			capacities: HashMap::new(),
+			pantries: HashMap::new()
+		}
+	}
+
+	pub fn set_capacity<T: 'static>(&mut self, capacity: usize) {
+		self.capacities.insert(TypeId::of::<T>(), capacity);
+	}
+
+	// None, если хранилище типа T заполнено
+	pub fn alloc<T: 'static>(&mut self, data: T) -> Option<BhvHandle> {
+		let type_id = TypeId::of::<T>();
+		let capacity = *self.capacities.get(&type_id).unwrap_or(&self.default_capacity);
+		let pantry = self.pantries.entry(type_id)
+			.or_insert_with(|| Box::new(Pantry::<T>::create(capacity)))
+			.as_any_mut().downcast_mut::<Pantry<T>>().unwrap();
+		let index = pantry.alloc(data);
+		if index == pantry.capacity {
+			return None;
+		}
+		Some(BhvHandle { type_id: type_id, index: index })
+	}
+
+	// две записи разом; если второй не нашлось места, первая освобождается
+	pub fn alloc_pair<A: 'static, B: 'static>(&mut self, a: A, b: B) -> Option<BhvDataSet> {
+		let handle_a = self.alloc(a)?;
+		match self.alloc(b) {
+			Some(handle_b) => Some(BhvDataSet::new(&[handle_a, handle_b])),
+			None => {
+				self.free(handle_a);
+				None
+			}
+		}
+	}
+
+	pub fn get<T: 'static>(&self, handle: BhvHandle) -> &T {
+		self.pantries.get(&handle.type_id)
+			.and_then(|p| p.as_any().downcast_ref::<Pantry<T>>())
+			.unwrap_or_else(|| panic!("get({}): wrong handle", std::any::type_name::<T>()))
+			.get(handle.index)
+	}
+
+	pub fn get_mut<T: 'static>(&mut self, handle: BhvHandle) -> &mut T {
+		self.pantries.get_mut(&handle.type_id)
+			.and_then(|p| p.as_any_mut().downcast_mut::<Pantry<T>>())
+			.unwrap_or_else(|| panic!("get_mut({}): wrong handle", std::any::type_name::<T>()))
+			.get_mut(handle.index)
+	}
+
+	pub fn free(&mut self, handle: BhvHandle) {
+		match self.pantries.get_mut(&handle.type_id) {
+			Some(pantry) => pantry.free(handle.index),
+			None => panic!("free: handle {} from unknown storage", handle.index)
+		}
+	}
+
+	pub fn free_set(&mut self, set: &BhvDataSet) {
+		for handle in set.handles.iter() {
+			self.free(*handle);
+		}
+	}
+
+	pub fn clear(&mut self) {
+		for pantry in self.pantries.values_mut() {
+			pantry.clear();
+		}
+	}
+
+	// занятые записи, которые никто не освободил
+	pub fn leak_report(&self) -> Vec<(&'static str, Vec<usize>)> {
+		let mut report = Vec::new();
+		for pantry in self.pantries.values() {
+			let indices = pantry.used_indices();
+			if !indices.is_empty() {
+				report.push((pantry.type_name(), indices));
+			}
+		}
+		report.sort_by_key(|(name, _)| *name);
+		report
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn alloc_fails_when_full() {
+		let mut storage = Storage::create(4);
+		storage.set_capacity::<u8>(2);
+		let first = storage.alloc(1u8).unwrap();
+		assert!(storage.alloc(2u8).is_some());
+		assert!(storage.alloc(3u8).is_none());
+		storage.free(first);
+		let handle = storage.alloc(4u8).unwrap();
+		assert_eq!(*storage.get::<u8>(handle), 4);
+	}
+
+	#[test]
+	fn alloc_pair_releases_first_on_failure() {
+		let mut storage = Storage::create(4);
+		storage.set_capacity::<u16>(0);
+		assert!(storage.alloc_pair(1u8, 2u16).is_none());
+		assert!(storage.leak_report().is_empty());
+		let set = storage.alloc_pair(1u8, 2u32).unwrap();
+		assert_eq!(*storage.get::<u32>(set.get::<u32>()), 2);
+	}
+}
@@ -24,8 +24,52 @@ impl XRand {
 		}
 	}
 
+	// одинаковое зерно даёт одинаковую последовательность (повторы, тесты)
+	pub fn from_seed(seed: u32) -> Self {
+		let mut rand = Self { s0: 0, s1: 0, s2: 0, s3: 0 };
+		rand.set_state(seed);
+		rand
+	}
+
+	fn set_state(&mut self, seed: u32) {
+		// splitmix32, чтобы нулевое состояние было недостижимо
+		let mut z = seed;
+		let mut next = || {
+			z = z.wrapping_add(0x9e3779b9);
+			let mut x = z;
+			x = (x ^ (x >> 16)).wrapping_mul(0x85ebca6b);
+			x = (x ^ (x >> 13)).wrapping_mul(0xc2b2ae35);
+			x ^ (x >> 16)
+		};
+		self.s0 = next();
+		self.s1 = next();
+		self.s2 = next();
+		self.s3 = next() | 1;
+	}
+
+	pub fn save(&self, out: &mut Vec<u8>) {
+		for s in [self.s0, self.s1, self.s2, self.s3].iter() {
+			out.extend_from_slice(&s.to_le_bytes());
+		}
+	}
+
+	// возвращает число прочитанных байт
+	pub fn load(&mut self, data: &[u8]) -> usize {
+		let mut s = [0u32; 4];
+		for (i, v) in s.iter_mut().enumerate() {
+			let mut bytes = [0u8; 4];
+			bytes.copy_from_slice(&data[i * 4..i * 4 + 4]);
+			*v = u32::from_le_bytes(bytes);
+		}
+		self.s0 = s[0];
+		self.s1 = s[1];
+		self.s2 = s[2];
+		self.s3 = s[3];
+		16
+	}
+
 	pub fn randint(& mut self, min: u32, max:u32) -> u32 {
-		let result:u32 = self.rotl(self.s1 * 5, 7) * 9;
+		let result:u32 = self.rotl(self.s1.wrapping_mul(5), 7).wrapping_mul(9);
 
 		let t: u32 = self.s1 << 9;
 		self.s2 ^= self.s0;